@@ -0,0 +1,6 @@
+-- Base marker rows used to be stored as a `null` delta, which clashed with a
+-- config legitimately setting a key to null. Mark them explicitly instead and
+-- give them an empty delta, a null anywhere in a delta now means "set to null".
+ALTER TABLE Deltas ADD COLUMN is_base BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE Deltas SET is_base = TRUE, delta = json('{}') WHERE json_type(delta) = 'null';
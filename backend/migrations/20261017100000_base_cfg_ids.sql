@@ -0,0 +1,38 @@
+-- Shapes used to be a global primary key, so two config names with the same key
+-- structure shared one base. Bases now have their own id and a shape is only
+-- unique within a config name. Deltas reference their base by that id.
+--
+-- Deltas that were stored against another name's base stay with that base, the
+-- old schema has no record of which name they were added under.
+CREATE TABLE NewBaseCfgs(
+    id INTEGER NOT NULL PRIMARY KEY,
+    cfg_hash TEXT NOT NULL,
+    name TEXT NOT NULL,
+    cfg JSON NOT NULL,
+    version INTEGER NOT NULL,
+    UNIQUE(name, version),
+    UNIQUE(name, cfg_hash)
+);
+
+INSERT INTO NewBaseCfgs (cfg_hash, name, cfg, version)
+SELECT cfg_hash, name, cfg, version FROM BaseCfgs ORDER BY name, version;
+
+CREATE TABLE NewDeltas (
+    id INTEGER NOT NULL PRIMARY KEY,
+    base_id INTEGER NOT NULL,
+    delta JSON NOT NULL,
+    is_base BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY(base_id) REFERENCES NewBaseCfgs(id)
+);
+
+INSERT INTO NewDeltas (id, base_id, delta, is_base, created_at)
+SELECT Deltas.id, NewBaseCfgs.id, Deltas.delta, Deltas.is_base, Deltas.created_at
+FROM Deltas INNER JOIN NewBaseCfgs ON Deltas.cfg_hash = NewBaseCfgs.cfg_hash;
+
+DROP TABLE Deltas;
+DROP TABLE BaseCfgs;
+ALTER TABLE NewBaseCfgs RENAME TO BaseCfgs;
+ALTER TABLE NewDeltas RENAME TO Deltas;
+
+CREATE INDEX DeltasBaseId ON Deltas(base_id);
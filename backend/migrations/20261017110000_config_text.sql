@@ -0,0 +1,10 @@
+-- Full text index over every stored config, one `key.path = value` line per leaf
+-- of the config each delta rebuilds to. The trigram tokenizer makes substring
+-- searches with LIKE use the index. Rows are filled in by the store, as configs
+-- can't be rebuilt from their deltas in sql.
+CREATE VIRTUAL TABLE ConfigText USING fts5(
+    line,
+    path UNINDEXED,
+    delta_id UNINDEXED,
+    tokenize = 'trigram'
+);
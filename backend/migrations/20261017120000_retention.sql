@@ -0,0 +1,17 @@
+-- Labels on stored configs, eg. a release name. Retention policies can keep
+-- every tagged config.
+CREATE TABLE Tags(
+    delta_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    UNIQUE(delta_id, tag),
+    FOREIGN KEY(delta_id) REFERENCES Deltas(id)
+);
+
+-- Which configs of a family `gc` keeps, a config is kept if any rule keeps it.
+-- Families without a policy are never expired.
+CREATE TABLE Retention(
+    name TEXT NOT NULL PRIMARY KEY,
+    keep_last INTEGER,
+    keep_days INTEGER,
+    keep_tagged BOOLEAN NOT NULL DEFAULT FALSE
+);
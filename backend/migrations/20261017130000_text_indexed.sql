@@ -0,0 +1,8 @@
+-- Whether a delta's config has its lines in ConfigText. Opening the store only
+-- looks up unindexed rows through the partial index instead of comparing every
+-- delta against ConfigText.
+ALTER TABLE Deltas ADD COLUMN text_indexed BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE Deltas SET text_indexed = TRUE WHERE id IN (SELECT delta_id FROM ConfigText);
+
+CREATE INDEX DeltasUnindexed ON Deltas(id) WHERE NOT text_indexed;
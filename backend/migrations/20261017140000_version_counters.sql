@@ -0,0 +1,10 @@
+-- The highest version ever given to each config name. Versions are numbered from
+-- it rather than from the versions still stored, so the number of a removed
+-- version isn't given to a different shape later.
+CREATE TABLE VersionCounters(
+    name TEXT NOT NULL PRIMARY KEY,
+    last_version INTEGER NOT NULL
+);
+
+INSERT INTO VersionCounters (name, last_version)
+SELECT name, MAX(version) FROM BaseCfgs GROUP BY name;
@@ -0,0 +1,6 @@
+-- The file each config name was last added from, so adding another file under
+-- the same name is reported even when it happens in a later run.
+CREATE TABLE ConfigSources(
+    name TEXT NOT NULL PRIMARY KEY,
+    path TEXT NOT NULL
+);
@@ -0,0 +1,14 @@
+SELECT
+    Deltas.id,
+    Deltas.delta AS "delta: Value",
+    BaseCfgs.cfg AS "cfg: Value"
+FROM
+    Deltas
+    INNER JOIN BaseCfgs ON Deltas.base_id = BaseCfgs.id
+WHERE
+    BaseCfgs.name = $1
+ORDER BY
+    Deltas.created_at DESC,
+    Deltas.id DESC
+LIMIT
+    1;
@@ -1,29 +1,13 @@
-WITH TmpDelta(delta, id, cfg_hash) AS (
-    SELECT
-        delta,
-        id,
-        cfg_hash
-    FROM
-        Deltas
-    WHERE
-        cfg_hash = (
-            SELECT
-                cfg_hash
-            FROM
-                BaseCfgs
-            WHERE
-                name = $1
-                AND version = $2
-        )
-)
 SELECT
-    delta AS "delta: Value", cfg_hash
+    Deltas.delta AS "delta: Value",
+    BaseCfgs.cfg AS "cfg: Value"
 FROM
-    TmpDelta
+    Deltas
+    INNER JOIN BaseCfgs ON Deltas.base_id = BaseCfgs.id
 WHERE
-    id = (
-        SELECT
-            MAX(id)
-        FROM
-            TmpDelta
-    );
+    BaseCfgs.name = $1
+    AND BaseCfgs.version = $2
+ORDER BY
+    Deltas.id DESC
+LIMIT
+    1;
@@ -0,0 +1,193 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+};
+
+/// One difference between two configs, at a key path such as `server.hosts[0]`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    Added {
+        path: String,
+        new: Value,
+    },
+    Removed {
+        path: String,
+        old: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } => path,
+            Change::Changed { path, .. } => path,
+        }
+    }
+}
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, new } => write!(f, "+ {}: {}", path, new),
+            Change::Removed { path, old } => write!(f, "- {}: {}", path, old),
+            Change::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// The key paths that differ between two configs, in key order.
+///
+/// Unlike `calculate_delta` the configs may have different shapes, a key only
+/// in `new` is added, a key only in `old` is removed and anything else that
+/// differs is changed. Array elements are compared by index.
+pub fn diff_configs(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_values(old, new, String::new(), &mut changes);
+    changes
+}
+fn diff_values(old: &Value, new: &Value, path: String, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old_obj), Value::Object(new_obj)) => {
+            let keys = old_obj
+                .keys()
+                .chain(new_obj.keys())
+                .collect::<BTreeSet<_>>();
+            for key in keys {
+                let child = key_path(&path, key);
+                match (old_obj.get(key), new_obj.get(key)) {
+                    (Some(old_v), Some(new_v)) => diff_values(old_v, new_v, child, changes),
+                    (Some(old_v), None) => changes.push(Change::Removed {
+                        path: child,
+                        old: old_v.clone(),
+                    }),
+                    (None, Some(new_v)) => changes.push(Change::Added {
+                        path: child,
+                        new: new_v.clone(),
+                    }),
+                    (None, None) => (),
+                }
+            }
+        }
+        (Value::Array(old_arr), Value::Array(new_arr)) => {
+            for (idx, old_v) in old_arr.iter().enumerate() {
+                let child = format!("{}[{}]", path, idx);
+                match new_arr.get(idx) {
+                    Some(new_v) => diff_values(old_v, new_v, child, changes),
+                    None => changes.push(Change::Removed {
+                        path: child,
+                        old: old_v.clone(),
+                    }),
+                }
+            }
+            for (idx, new_v) in new_arr.iter().enumerate().skip(old_arr.len()) {
+                changes.push(Change::Added {
+                    path: format!("{}[{}]", path, idx),
+                    new: new_v.clone(),
+                });
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => (),
+    }
+}
+/// Every leaf of a config with its key path, empty objects and arrays are leaves.
+pub(crate) fn leaf_paths(cfg: &Value) -> Vec<(String, &Value)> {
+    fn walk<'a>(value: &'a Value, path: String, leaves: &mut Vec<(String, &'a Value)>) {
+        match value {
+            Value::Object(o) if !o.is_empty() => o
+                .iter()
+                .for_each(|(k, v)| walk(v, key_path(&path, k), leaves)),
+            Value::Array(a) if !a.is_empty() => a
+                .iter()
+                .enumerate()
+                .for_each(|(i, v)| walk(v, format!("{}[{}]", path, i), leaves)),
+            v => leaves.push((path, v)),
+        }
+    }
+    let mut leaves = vec![];
+    walk(cfg, String::new(), &mut leaves);
+    leaves
+}
+/// Append a key to a path, keys that aren't plain words are quoted as `["a.b"]`.
+fn key_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    match (plain, path.is_empty()) {
+        (true, true) => key.to_string(),
+        (true, false) => format!("{}.{}", path, key),
+        (false, _) => format!("{}[{}]", path, Value::from(key)),
+    }
+}
+
+#[cfg(test)]
+mod test_diff {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_same_shape() {
+        let old = json!({"a": {"b": 1, "c": [1, 2]}, "d": "x"});
+        let new = json!({"a": {"b": 2, "c": [1, 3]}, "d": "x"});
+        assert_eq!(
+            diff_configs(&old, &new),
+            vec![
+                Change::Changed {
+                    path: String::from("a.b"),
+                    old: json!(1),
+                    new: json!(2)
+                },
+                Change::Changed {
+                    path: String::from("a.c[1]"),
+                    old: json!(2),
+                    new: json!(3)
+                },
+            ]
+        );
+        assert!(diff_configs(&old, &old).is_empty());
+    }
+    #[test]
+    fn test_leaf_paths() {
+        let cfg = json!({"a": {"b": [1, {"c": null}], "d": {}}, "e f": []});
+        assert_eq!(
+            leaf_paths(&cfg),
+            vec![
+                (String::from("a.b[0]"), &json!(1)),
+                (String::from("a.b[1].c"), &json!(null)),
+                (String::from("a.d"), &json!({})),
+                (String::from("[\"e f\"]"), &json!([])),
+            ]
+        );
+    }
+    #[test]
+    fn test_diff_across_shapes() {
+        let old = json!({"a": {"b": 1}, "gone": true, "list": [1, 2], "x.y": 0});
+        let new = json!({"a": 5, "added": {"z": null}, "list": [1, 2, 3], "x.y": 1});
+        let changes = diff_configs(&old, &new);
+        assert_eq!(
+            changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec![
+                "~ a: {\"b\":1} -> 5",
+                "+ added: {\"z\":null}",
+                "- gone: true",
+                "+ list[2]: 3",
+                "~ [\"x.y\"]: 0 -> 1",
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[1]).unwrap(),
+            json!({"kind": "added", "path": "added", "new": {"z": null}})
+        );
+    }
+}
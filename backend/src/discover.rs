@@ -0,0 +1,247 @@
+use crate::{DeltaError, FormatRegistry, Result};
+use globset::GlobBuilder;
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
+use std::path::{Component, Path, PathBuf};
+
+/// Gitignore style file of paths to skip while walking directories, honoured in
+/// the walked directory, its subdirectories and its parents.
+pub const IGNORE_FILE: &str = ".deltaignore";
+
+/// Gitignore style globs narrowing the files found in directories and glob patterns.
+/// Paths passed directly are never filtered.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    /// Only files matching one of these are kept, if any are given.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Expand directories and glob patterns into the config files they hold.
+///
+/// Directories are walked recursively for files with a registered extension, glob
+/// patterns such as `example_data/**/*.yaml` keep every file they match. Any other
+/// path is passed through as is. The result is sorted per input and deduplicated.
+pub fn expand_paths(
+    inputs: &[PathBuf],
+    filter: &PathFilter,
+    formats: &FormatRegistry,
+) -> Result<Vec<PathBuf>> {
+    let mut out = vec![];
+    for input in inputs {
+        let found = if input.is_dir() {
+            walk(input, filter)?
+                .into_iter()
+                .filter(|p| formats.by_path(p).is_some())
+                .collect()
+        } else if let (false, Some((base, pattern))) = (input.exists(), split_glob(input)) {
+            let glob = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| DeltaError::InvalidPattern {
+                    pattern: pattern.clone(),
+                    source: e.into(),
+                })?
+                .compile_matcher();
+            walk(&base, filter)?
+                .into_iter()
+                .filter(|p| glob.is_match(p))
+                .collect()
+        } else {
+            vec![input.clone()]
+        };
+        for path in found {
+            if !out.contains(&path) {
+                out.push(path);
+            }
+        }
+    }
+    Ok(out)
+}
+/// The directories to watch for changes to the files `expand_paths` finds, and
+/// whether to watch them recursively. Files are watched through their parent
+/// directory, as editors often save by replacing the file.
+pub fn watch_roots(inputs: &[PathBuf]) -> Vec<(PathBuf, bool)> {
+    let mut roots = vec![];
+    for input in inputs {
+        let root = if input.is_dir() {
+            (input.clone(), true)
+        } else if let (false, Some((base, _))) = (input.exists(), split_glob(input)) {
+            (base, true)
+        } else {
+            let parent = input.parent().filter(|p| !p.as_os_str().is_empty());
+            (parent.unwrap_or(Path::new(".")).to_path_buf(), false)
+        };
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+/// Every file below `root` that isn't filtered or ignored.
+fn walk(root: &Path, filter: &PathFilter) -> Result<Vec<PathBuf>> {
+    let mut overrides = OverrideBuilder::new(root);
+    let include = filter.include.iter().map(|p| (p, p.clone()));
+    let exclude = filter.exclude.iter().map(|p| (p, format!("!{}", p)));
+    for (pattern, glob) in include.chain(exclude) {
+        overrides
+            .add(&glob)
+            .map_err(|e| DeltaError::InvalidPattern {
+                pattern: pattern.clone(),
+                source: e.into(),
+            })?;
+    }
+    let overrides = overrides.build().map_err(|e| DeltaError::InvalidPattern {
+        pattern: filter.include.join(", "),
+        source: e.into(),
+    })?;
+    let walker = WalkBuilder::new(root)
+        .standard_filters(false)
+        .parents(true)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .overrides(overrides)
+        .filter_entry(|e| e.file_name() != ".git")
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    let mut files = vec![];
+    for entry in walker {
+        let entry = entry.map_err(|e| DeltaError::Io(std::io::Error::other(e)))?;
+        if entry.file_type().is_some_and(|t| t.is_file()) {
+            let path = entry.path();
+            files.push(path.strip_prefix("./").unwrap_or(path).to_path_buf());
+        }
+    }
+    Ok(files)
+}
+/// Split a glob pattern into the directory before its first wildcard and the
+/// pattern itself, `None` if the path has no wildcards.
+fn split_glob(path: &Path) -> Option<(PathBuf, String)> {
+    let is_glob = |c: &Component| {
+        c.as_os_str()
+            .to_str()
+            .is_some_and(|s| s.contains(['*', '?', '[', '{']))
+    };
+    let first_glob = path.components().position(|c| is_glob(&c))?;
+    let base = path.components().take(first_glob).collect::<PathBuf>();
+    let pattern = path.to_str()?.strip_prefix("./").unwrap_or(path.to_str()?);
+    match base.as_os_str().is_empty() {
+        true => Some((PathBuf::from("."), pattern.to_string())),
+        false => Some((base, pattern.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test_discover {
+    use super::*;
+    use std::fs;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in [
+            "a.yaml",
+            "b.json",
+            "notes.txt",
+            ".env",
+            "nested/c.yaml",
+            "nested/deep/d.toml",
+            "skipped/e.yaml",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "a: 1").unwrap();
+        }
+        fs::write(dir.path().join(IGNORE_FILE), "skipped/\n").unwrap();
+        dir
+    }
+    fn relative(root: &Path, paths: Vec<PathBuf>) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap().to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let dir = tree();
+        let formats = FormatRegistry::default();
+        let root = dir.path().to_path_buf();
+        let found = expand_paths(
+            &[dir.path().to_path_buf()],
+            &PathFilter::default(),
+            &formats,
+        )
+        .unwrap();
+        assert_eq!(
+            relative(&root, found),
+            vec![
+                ".env",
+                "a.yaml",
+                "b.json",
+                "nested/c.yaml",
+                "nested/deep/d.toml"
+            ]
+        );
+        let filter = PathFilter {
+            include: vec![String::from("*.yaml"), String::from("*.toml")],
+            exclude: vec![String::from("deep/")],
+        };
+        let found = expand_paths(&[dir.path().to_path_buf()], &filter, &formats).unwrap();
+        assert_eq!(relative(&root, found), vec!["a.yaml", "nested/c.yaml"]);
+    }
+    #[test]
+    fn test_expand_glob() {
+        let dir = tree();
+        let formats = FormatRegistry::default();
+        let root = dir.path().to_path_buf();
+        let inputs = [
+            root.join("**/*.yaml"),
+            root.join("*.txt"),
+            root.join("a.yaml"),
+            root.join("missing.yaml"),
+        ];
+        let found = expand_paths(&inputs, &PathFilter::default(), &formats).unwrap();
+        assert_eq!(
+            relative(&root, found),
+            vec!["a.yaml", "nested/c.yaml", "notes.txt", "missing.yaml"]
+        );
+        assert!(matches!(
+            expand_paths(&[root.join("[a.yaml")], &PathFilter::default(), &formats),
+            Err(DeltaError::InvalidPattern { .. })
+        ));
+    }
+    #[test]
+    fn test_watch_roots() {
+        let dir = tree();
+        let root = dir.path().to_path_buf();
+        let inputs = [
+            root.join("nested"),
+            root.join("a.yaml"),
+            root.join("b.json"),
+            root.join("skipped/*.yaml"),
+            PathBuf::from("run.yaml"),
+        ];
+        assert_eq!(
+            watch_roots(&inputs),
+            vec![
+                (root.join("nested"), true),
+                (root.clone(), false),
+                (root.join("skipped"), true),
+                (PathBuf::from("."), false)
+            ]
+        );
+    }
+    #[test]
+    fn test_split_glob() {
+        assert_eq!(
+            split_glob(Path::new("example_data/**/*.yaml")),
+            Some((
+                PathBuf::from("example_data"),
+                String::from("example_data/**/*.yaml")
+            ))
+        );
+        assert_eq!(
+            split_glob(Path::new("./*.yaml")),
+            Some((PathBuf::from("."), String::from("*.yaml")))
+        );
+        assert_eq!(split_glob(Path::new("a/b.yaml")), None);
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+/// Errors returned by the config store and the config file readers.
+#[derive(Debug, thiserror::Error)]
+pub enum DeltaError {
+    #[error(
+        "No config found with name {name} and version {}\nAvailable Configs:\n{}",
+        fmt_version(.version),
+        fmt_available(.available)
+    )]
+    ConfigNotFound {
+        name: String,
+        version: Option<i64>,
+        /// Every stored `(name, version)`, to help spot typos.
+        available: Vec<(String, i64)>,
+    },
+    #[error("No delta found with id {0}")]
+    DeltaNotFound(i64),
+    #[error("Path is unparseable {}", .0.display())]
+    InvalidPath(PathBuf),
+    #[error("No registered config format matches '{0}'")]
+    UnsupportedFormat(String),
+    #[error("While opening .{format} an error occured.")]
+    Parse {
+        format: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("While rendering as {format} an error occured.")]
+    Render {
+        format: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Invalid path pattern {pattern}")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Invalid query, {0}")]
+    InvalidQuery(String),
+    #[error("Invalid retention policy, {0}")]
+    InvalidPolicy(String),
+    #[error("Key structure must be the same in the jsons.")]
+    ShapeMismatch,
+    #[error("Json object, should be a tree not a single leaf.")]
+    InvalidRootType,
+    #[error("A database operation failed.")]
+    Database(#[from] sqlx::Error),
+    #[error("Migrating the database failed.")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, DeltaError>;
+
+fn fmt_version(version: &Option<i64>) -> String {
+    match version {
+        Some(v) => format!("{}", v),
+        None => String::from("latest"),
+    }
+}
+fn fmt_available(available: &[(String, i64)]) -> String {
+    available
+        .iter()
+        .map(|(n, ver)| format!("{}:{}\n", n, ver))
+        .reduce(|s, i| s + &i)
+        .unwrap_or(String::from("No configs stored."))
+}
@@ -0,0 +1,724 @@
+use crate::DeltaError;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{ffi::OsStr, path::Path};
+
+/// Error type returned by format implementations, wrapped in `DeltaError::Parse`
+/// or `DeltaError::Render` by the registry.
+pub type ParseError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A config file format that can be read into json and written back out.
+///
+/// Implement this to store in-house formats and register it on a `FormatRegistry`.
+pub trait ConfigFormat: Send + Sync {
+    /// Name used to select the format, eg. for `--format`.
+    fn name(&self) -> &str;
+    /// File extensions handled by this format, without the leading dot.
+    fn extensions(&self) -> &[&str];
+    /// Whether the contents look like this format, used for files whose
+    /// extension isn't registered.
+    fn sniff(&self, _contents: &str) -> bool {
+        false
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError>;
+    /// Parse a file holding several configs, eg. a yaml stream split by `---`.
+    /// Formats without documents hold exactly one config.
+    fn parse_documents(&self, contents: &str) -> Result<Vec<Value>, ParseError> {
+        Ok(vec![self.parse(contents)?])
+    }
+    fn render(&self, cfg: &Value) -> Result<String, ParseError>;
+    /// Render for people to read, formats without a compact form render as usual.
+    fn render_pretty(&self, cfg: &Value) -> Result<String, ParseError> {
+        self.render(cfg)
+    }
+}
+
+/// The set of formats configs are read and rendered with.
+///
+/// Formats registered later take precedence, both for extensions and sniffing,
+/// so a built-in format can be overridden.
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn ConfigFormat>>,
+}
+impl Default for FormatRegistry {
+    /// A registry holding the built-in formats.
+    fn default() -> Self {
+        let mut r = Self::empty();
+        r.register(Properties);
+        r.register(Env);
+        r.register(Yaml);
+        r.register(Toml);
+        r.register(Json5);
+        r.register(Json);
+        r
+    }
+}
+impl FormatRegistry {
+    /// A registry without any formats.
+    pub fn empty() -> Self {
+        Self { formats: vec![] }
+    }
+    pub fn register(&mut self, format: impl ConfigFormat + 'static) -> &mut Self {
+        self.formats.push(Box::new(format));
+        self
+    }
+    fn iter(&self) -> impl Iterator<Item = &dyn ConfigFormat> {
+        self.formats.iter().rev().map(|f| f.as_ref())
+    }
+    /// Every registered format name, built-ins included.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.iter().map(|f| f.name()).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+    pub fn by_name(&self, name: &str) -> crate::Result<&dyn ConfigFormat> {
+        self.iter()
+            .find(|f| f.name() == name)
+            .ok_or_else(|| DeltaError::UnsupportedFormat(name.to_string()))
+    }
+    pub fn by_extension(&self, ext: &str) -> Option<&dyn ConfigFormat> {
+        self.iter().find(|f| f.extensions().contains(&ext))
+    }
+    pub fn sniff(&self, contents: &str) -> Option<&dyn ConfigFormat> {
+        self.iter().find(|f| f.sniff(contents))
+    }
+    /// Read a config file into json. The format is picked by the file extension,
+    /// falling back to sniffing the contents if the extension isn't registered.
+    pub fn read_file(&self, path: impl AsRef<Path>) -> crate::Result<Value> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        self.parse(self.format_of(path, &contents)?, &contents)
+    }
+    /// Read every document of a config file, see `ConfigFormat::parse_documents`.
+    pub fn read_documents(&self, path: impl AsRef<Path>) -> crate::Result<Vec<Value>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        self.parse_documents(self.format_of(path, &contents)?, &contents)
+    }
+    /// The format registered for a path's extension, without sniffing its contents.
+    pub fn by_path(&self, path: &Path) -> Option<&dyn ConfigFormat> {
+        self.by_extension(file_format(path).ok()??)
+    }
+    fn format_of(&self, path: &Path, contents: &str) -> crate::Result<&dyn ConfigFormat> {
+        match self.by_path(path) {
+            Some(f) => Ok(f),
+            None => self.sniff(contents).ok_or_else(|| {
+                let ext = file_format(path).ok().flatten().unwrap_or_default();
+                DeltaError::UnsupportedFormat(ext.to_string())
+            }),
+        }
+    }
+    pub fn parse(&self, format: &dyn ConfigFormat, contents: &str) -> crate::Result<Value> {
+        format.parse(contents).map_err(|e| DeltaError::Parse {
+            format: format.name().to_string(),
+            source: e,
+        })
+    }
+    pub fn parse_documents(
+        &self,
+        format: &dyn ConfigFormat,
+        contents: &str,
+    ) -> crate::Result<Vec<Value>> {
+        format
+            .parse_documents(contents)
+            .map_err(|e| DeltaError::Parse {
+                format: format.name().to_string(),
+                source: e,
+            })
+    }
+    /// Render a config with the format registered under `name`.
+    pub fn render(&self, name: &str, cfg: &Value) -> crate::Result<String> {
+        let format = self.by_name(name)?;
+        format.render(cfg).map_err(|e| DeltaError::Render {
+            format: format.name().to_string(),
+            source: e,
+        })
+    }
+    /// Render a config for people to read, see `ConfigFormat::render_pretty`.
+    pub fn render_pretty(&self, name: &str, cfg: &Value) -> crate::Result<String> {
+        let format = self.by_name(name)?;
+        format.render_pretty(cfg).map_err(|e| DeltaError::Render {
+            format: format.name().to_string(),
+            source: e,
+        })
+    }
+}
+/// The extension of a config file. Dotenv files are commonly named `.env`
+/// or `.env.<stage>`, which are treated as `env`.
+fn file_format(path: &Path) -> crate::Result<Option<&str>> {
+    let f_name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| DeltaError::InvalidPath(path.to_path_buf()))?;
+    if f_name == ".env" || f_name.starts_with(".env.") {
+        return Ok(Some("env"));
+    }
+    Ok(path.extension().and_then(OsStr::to_str))
+}
+
+struct Yaml;
+impl ConfigFormat for Yaml {
+    fn name(&self) -> &str {
+        "yaml"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["yaml", "yml"]
+    }
+    /// Almost any text is a yaml scalar, so only a mapping as first document counts.
+    fn sniff(&self, contents: &str) -> bool {
+        serde_yaml::Deserializer::from_str(contents)
+            .next()
+            .is_some_and(|doc| matches!(Value::deserialize(doc), Ok(Value::Object(_))))
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+    /// Empty documents, eg. from a leading or trailing `---`, are skipped.
+    fn parse_documents(&self, contents: &str) -> Result<Vec<Value>, ParseError> {
+        let mut docs = vec![];
+        for doc in serde_yaml::Deserializer::from_str(contents) {
+            match Value::deserialize(doc)? {
+                Value::Null => (),
+                v => docs.push(v),
+            }
+        }
+        Ok(docs)
+    }
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        Ok(serde_yaml::to_string(cfg)?)
+    }
+}
+struct Json;
+impl ConfigFormat for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+    fn sniff(&self, contents: &str) -> bool {
+        contents.trim_start().starts_with('{') && serde_json::from_str::<Value>(contents).is_ok()
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        Ok(serde_json::from_str(contents)?)
+    }
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        Ok(serde_json::to_string(cfg)?)
+    }
+    fn render_pretty(&self, cfg: &Value) -> Result<String, ParseError> {
+        Ok(serde_json::to_string_pretty(cfg)?)
+    }
+}
+/// Json5 is rendered as pretty json, which is valid json5.
+struct Json5;
+impl ConfigFormat for Json5 {
+    fn name(&self) -> &str {
+        "json5"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["json5", "jsonc"]
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        parse_json5(contents)
+    }
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        Ok(serde_json::to_string_pretty(cfg)?)
+    }
+}
+struct Toml;
+impl ConfigFormat for Toml {
+    fn name(&self) -> &str {
+        "toml"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+    fn sniff(&self, contents: &str) -> bool {
+        parse_toml(contents).is_ok()
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        parse_toml(contents)
+    }
+    /// Toml has no null, so configs containing one fail to render.
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        Ok(toml::to_string(cfg)?)
+    }
+}
+struct Env;
+impl ConfigFormat for Env {
+    fn name(&self) -> &str {
+        "env"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["env"]
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        parse_env(contents)
+    }
+    /// Nested keys are joined with `__`, as most env based config loaders expect.
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        let mut out = String::new();
+        for (key, value) in flatten(cfg, "__")? {
+            out.push_str(&format!("{}={}\n", key, quote_env(&value)));
+        }
+        Ok(out)
+    }
+}
+struct Properties;
+impl ConfigFormat for Properties {
+    fn name(&self) -> &str {
+        "properties"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["properties"]
+    }
+    fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+        parse_properties(contents)
+    }
+    fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+        let mut out = String::new();
+        for (key, value) in flatten(cfg, ".")? {
+            out.push_str(&format!(
+                "{}={}\n",
+                escape_property(&key, true),
+                escape_property(&value, false)
+            ));
+        }
+        Ok(out)
+    }
+}
+
+/// Flatten a config into `(key path, value)` pairs for the line based formats,
+/// array elements use their index as the key.
+fn flatten(cfg: &Value, sep: &str) -> Result<Vec<(String, String)>, ParseError> {
+    fn walk(value: &Value, prefix: String, sep: &str, out: &mut Vec<(String, String)>) {
+        let child = |k: &str| match prefix.is_empty() {
+            true => k.to_string(),
+            false => format!("{}{}{}", prefix, sep, k),
+        };
+        match value {
+            Value::Object(o) => o.iter().for_each(|(k, v)| walk(v, child(k), sep, out)),
+            Value::Array(a) => a
+                .iter()
+                .enumerate()
+                .for_each(|(i, v)| walk(v, child(&i.to_string()), sep, out)),
+            Value::String(s) => out.push((prefix, s.clone())),
+            Value::Null => out.push((prefix, String::new())),
+            v => out.push((prefix, v.to_string())),
+        }
+    }
+    if !cfg.is_object() {
+        return Err("Only objects can be rendered as key value lines".into());
+    }
+    let mut out = vec![];
+    walk(cfg, String::new(), sep, &mut out);
+    Ok(out)
+}
+fn quote_env(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:@,+".contains(c);
+    if value.chars().all(plain) {
+        return value.to_string();
+    }
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' | '"' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+fn escape_property(s: &str, is_key: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '=' | ':' | '#' | '!' if is_key => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' ' if is_key || i == 0 => out.push_str("\\ "),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Parse toml, datetimes become their RFC 3339 strings.
+fn parse_toml(s: &str) -> Result<Value, ParseError> {
+    let table = s.parse::<toml::Table>()?;
+    Ok(toml_to_json(toml::Value::Table(table)))
+}
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(t) => {
+            Value::Object(t.into_iter().map(|(k, v)| (k, toml_to_json(v))).collect())
+        }
+    }
+}
+
+/// Parse json5, which covers jsonc's comments and trailing commas.
+fn parse_json5(s: &str) -> Result<Value, ParseError> {
+    Ok(json5::from_str(s)?)
+}
+
+/// Parse a dotenv file into a flat object of strings. Values are stored as written,
+/// `${VAR}` isn't interpolated so the stored config doesn't depend on the environment.
+fn parse_env(s: &str) -> Result<Value, ParseError> {
+    let mut out = Map::new();
+    let mut lines = s.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Line {} has no '='", idx + 1))?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("Line {} has an invalid key '{}'", idx + 1, key).into());
+        }
+        let mut value = value.trim_start().to_string();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let value = match quote {
+            Some(q) => {
+                // Quoted values run up to their closing quote, across lines.
+                while closing_quote(&value, q).is_none() {
+                    let (_, next) = lines
+                        .next()
+                        .ok_or_else(|| format!("Line {} has an unclosed quote", idx + 1))?;
+                    value.push('\n');
+                    value.push_str(next);
+                }
+                let inner = &value[1..closing_quote(&value, q).unwrap()];
+                match q {
+                    '"' => unescape_env(inner),
+                    _ => inner.to_string(),
+                }
+            }
+            None => match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.trim_end().to_string(),
+            },
+        };
+        out.insert(key.to_string(), Value::String(value));
+    }
+    Ok(Value::Object(out))
+}
+/// The byte index of the quote closing a value that starts with `quote`.
+fn closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in value.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote == '"' => escaped = true,
+            c if c == quote => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+fn unescape_env(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c @ ('\\' | '"' | '$')) => out.push(c),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse a java style `.properties` file into a flat object of strings.
+fn parse_properties(s: &str) -> Result<Value, ParseError> {
+    let mut out = Map::new();
+    let mut lines = s.lines();
+    while let Some(line) = lines.next() {
+        let mut logical = line.trim_start().to_string();
+        if logical.is_empty() || logical.starts_with('#') || logical.starts_with('!') {
+            continue;
+        }
+        while ends_with_continuation(&logical) {
+            logical.pop();
+            match lines.next() {
+                Some(next) => logical.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+        let (key, value) = split_property(&logical);
+        out.insert(
+            unescape_property(key)?,
+            Value::String(unescape_property(value)?),
+        );
+    }
+    Ok(Value::Object(out))
+}
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+/// Split at the first unescaped `=`, `:` or whitespace.
+fn split_property(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..i], line[i + 1..].trim_start()),
+            c if c.is_whitespace() => {
+                let rest = line[i..].trim_start();
+                let rest = rest
+                    .strip_prefix(['=', ':'])
+                    .map_or(rest, |r| r.trim_start());
+                return (&line[..i], rest);
+            }
+            _ => (),
+        }
+    }
+    (line, "")
+}
+fn unescape_property(s: &str) -> Result<String, ParseError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\u{c}'),
+            Some('u') => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                let invalid = || format!("Invalid unicode escape \\u{}", hex);
+                let code = u32::from_str_radix(&hex, 16).map_err(|_| invalid())?;
+                out.push(char::from_u32(code).ok_or_else(invalid)?);
+            }
+            Some(c) => out.push(c),
+            None => (),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test_formats {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_toml() {
+        let s = r#"
+        [package]
+        name = "delta"
+        version = 1
+        released = 2024-04-27T09:19:08Z
+
+        [[bin]]
+        path = "src/main.rs"
+        "#;
+        assert_eq!(
+            parse_toml(s).unwrap(),
+            json!({
+                "package": {"name": "delta", "version": 1, "released": "2024-04-27T09:19:08Z"},
+                "bin": [{"path": "src/main.rs"}]
+            })
+        );
+    }
+    #[test]
+    fn test_jsonc() {
+        let s = r#"{
+            // The port to listen on.
+            "port": 8080,
+            /* Trailing commas are fine */
+            "hosts": ["a", "b",],
+        }"#;
+        assert_eq!(
+            parse_json5(s).unwrap(),
+            json!({"port": 8080, "hosts": ["a", "b"]})
+        );
+    }
+    #[test]
+    fn test_env() {
+        let s = "# comment\nexport HOST=localhost\nPORT=\"8080\" # http\nURL=${HOST}:${PORT}\n\
+                 RAW='${HOME} \\n'\nMULTI=\"a\nb \\\"c\\\" \\$d\"\n";
+        assert_eq!(
+            parse_env(s).unwrap(),
+            json!({
+                "HOST": "localhost",
+                "PORT": "8080",
+                "URL": "${HOST}:${PORT}",
+                "RAW": "${HOME} \\n",
+                "MULTI": "a\nb \"c\" $d"
+            })
+        );
+        assert!(parse_env("NO_EQUALS\n").is_err());
+        assert!(parse_env("OPEN=\"never closed\n").is_err());
+        let env = Env;
+        let cfg = json!({"A": "x y", "B": "$HOME \"q\"\nz"});
+        assert_eq!(env.parse(&env.render(&cfg).unwrap()).unwrap(), cfg);
+    }
+    #[test]
+    fn test_yaml_documents() {
+        let r = FormatRegistry::default();
+        let yaml = r.by_name("yaml").unwrap();
+        let s = "---\nkind: Service\nport: 80\n---\nkind: Deployment\nreplicas: 2\n---\n";
+        assert_eq!(
+            r.parse_documents(yaml, s).unwrap(),
+            vec![
+                json!({"kind": "Service", "port": 80}),
+                json!({"kind": "Deployment", "replicas": 2})
+            ]
+        );
+        assert_eq!(r.sniff(s).unwrap().name(), "yaml");
+        assert_eq!(
+            r.parse_documents(r.by_name("json").unwrap(), "{\"a\": 1}")
+                .unwrap(),
+            vec![json!({"a": 1})]
+        );
+    }
+    #[test]
+    fn test_registry_lookup() {
+        let r = FormatRegistry::default();
+        assert_eq!(r.by_extension("yml").unwrap().name(), "yaml");
+        assert_eq!(r.by_extension("jsonc").unwrap().name(), "json5");
+        assert!(r.by_extension("ini").is_none());
+        assert_eq!(r.sniff("{\"a\": 1}").unwrap().name(), "json");
+        assert_eq!(r.sniff("[a]\nb = 1").unwrap().name(), "toml");
+        assert_eq!(r.sniff("a:\n  b: 1").unwrap().name(), "yaml");
+        assert!(r.sniff("just some text").is_none());
+        assert!(matches!(
+            r.by_name("ini"),
+            Err(DeltaError::UnsupportedFormat(_))
+        ));
+    }
+    #[test]
+    fn test_file_format() {
+        assert_eq!(file_format(Path::new("a/b.yaml")).unwrap(), Some("yaml"));
+        assert_eq!(
+            file_format(Path::new("pyproject.toml")).unwrap(),
+            Some("toml")
+        );
+        assert_eq!(file_format(Path::new("prod.env")).unwrap(), Some("env"));
+        assert_eq!(file_format(Path::new("a/.env")).unwrap(), Some("env"));
+        assert_eq!(
+            file_format(Path::new(".env.production")).unwrap(),
+            Some("env")
+        );
+        assert_eq!(file_format(Path::new("Makefile")).unwrap(), None);
+        assert!(matches!(
+            file_format(Path::new("..")),
+            Err(DeltaError::InvalidPath(_))
+        ));
+    }
+    /// A line based in-house format, `key -> value`.
+    struct Arrows;
+    impl ConfigFormat for Arrows {
+        fn name(&self) -> &str {
+            "arrows"
+        }
+        fn extensions(&self) -> &[&str] {
+            &["arrows", "yaml"]
+        }
+        fn sniff(&self, contents: &str) -> bool {
+            contents.contains(" -> ")
+        }
+        fn parse(&self, contents: &str) -> Result<Value, ParseError> {
+            let mut out = Map::new();
+            for line in contents.lines() {
+                let (k, v) = line.split_once(" -> ").ok_or("Missing arrow")?;
+                out.insert(k.to_string(), Value::String(v.to_string()));
+            }
+            Ok(Value::Object(out))
+        }
+        fn render(&self, cfg: &Value) -> Result<String, ParseError> {
+            Ok(flatten(cfg, ".")?
+                .into_iter()
+                .map(|(k, v)| format!("{} -> {}\n", k, v))
+                .collect())
+        }
+    }
+    #[test]
+    fn test_custom_format() {
+        let mut r = FormatRegistry::default();
+        r.register(Arrows);
+        assert_eq!(r.by_extension("yaml").unwrap().name(), "arrows");
+        assert_eq!(r.sniff("a -> b").unwrap().name(), "arrows");
+        let cfg = r.parse(r.by_name("arrows").unwrap(), "a -> b").unwrap();
+        assert_eq!(cfg, json!({"a": "b"}));
+        assert_eq!(r.render("arrows", &cfg).unwrap(), "a -> b\n");
+        assert!(matches!(
+            r.parse(r.by_name("arrows").unwrap(), "a b"),
+            Err(DeltaError::Parse { .. })
+        ));
+    }
+    #[test]
+    fn test_render_roundtrip() {
+        let r = FormatRegistry::default();
+        let cfg = json!({"a": {"b": 1, "c": [true, "x y"]}, "d": "$HOME \"q\"\n"});
+        for name in ["json", "json5", "yaml", "toml"] {
+            let s = r.render(name, &cfg).unwrap();
+            assert_eq!(r.parse(r.by_name(name).unwrap(), &s).unwrap(), cfg);
+        }
+        let env = r.render("env", &cfg).unwrap();
+        assert_eq!(
+            parse_env(&env).unwrap(),
+            json!({"a__b": "1", "a__c__0": "true", "a__c__1": "x y", "d": "$HOME \"q\"\n"})
+        );
+        let props = r.render("properties", &cfg).unwrap();
+        assert_eq!(
+            parse_properties(&props).unwrap(),
+            json!({"a.b": "1", "a.c.0": "true", "a.c.1": "x y", "d": "$HOME \"q\"\n"})
+        );
+        assert_eq!(
+            r.render_pretty("json", &json!({"a": 1})).unwrap(),
+            "{\n  \"a\": 1\n}"
+        );
+        assert_eq!(
+            r.render_pretty("yaml", &cfg).unwrap(),
+            r.render("yaml", &cfg).unwrap()
+        );
+        assert!(matches!(
+            r.render("toml", &json!({"a": null})),
+            Err(DeltaError::Render { .. })
+        ));
+    }
+    #[test]
+    fn test_properties() {
+        let s = "# comment\n! comment\na.b = 1\nc: two words\nd three\\\n    four\ne\\=f=\\u0041\n";
+        assert_eq!(
+            parse_properties(s).unwrap(),
+            json!({"a.b": "1", "c": "two words", "d": "threefour", "e=f": "A"})
+        );
+    }
+}
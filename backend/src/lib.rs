@@ -1,311 +1,42 @@
-use anyhow::{anyhow, Context};
-use serde_json::{to_string, Map, Value};
-use sqlx::{query_scalar, SqlitePool};
-use std::{
-    future::Future,
-    hash::{DefaultHasher, Hash, Hasher},
-    path::Path,
-};
-use tokio::runtime::Runtime;
-use tracing::{debug, info, warn};
-use tracing_subscriber::fmt::format;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+mod diff;
+mod discover;
+mod error;
+mod formats;
+mod naming;
+mod query;
+mod retention;
+mod store;
+pub use diff::{diff_configs, Change};
+pub use discover::{expand_paths, watch_roots, PathFilter, IGNORE_FILE};
+pub use error::{DeltaError, Result};
+pub use formats::{ConfigFormat, FormatRegistry, ParseError};
+pub use naming::{name_collisions, Naming};
+pub use retention::{GcReport, RetentionPolicy};
+pub use store::{AddOutcome, AsyncStore, BlameEntry, HistoryEntry, Store, TextHit};
 
-pub fn read_file(path: impl AsRef<Path>) -> anyhow::Result<Value> {
-    let path = path.as_ref();
-    let Some(f_type) = path.extension() else {
-        return Err(anyhow!("Path is unparseable {}", path.display()));
-    };
-    let f = std::fs::File::open(path)?;
-    let f_ext = f_type
-        .to_str()
-        .ok_or(anyhow!("Path is unparseable {}", path.display()))?;
-    match f_ext {
-        "yaml" => Ok(serde_yaml::from_reader(f).context("While opening .yaml an error occured.")?),
-        "json" => Ok(serde_json::from_reader(f).context("While opening .json an error occured.")?),
-        "yml" => Ok(serde_yaml::from_reader(f).context("While opening .yml an error occured.")?),
-        _ => Err(anyhow!(format!(
-            "File extension {} is not one of 'yaml', 'json', 'yml'",
-            f_ext
-        )))?,
-    }
-}
-pub struct Store {
-    pool: SqlitePool,
-    rt: tokio::runtime::Runtime,
-}
-impl Store {
-    pub fn block_on<F>(&self, f: F) -> F::Output
-    where
-        F: Future,
-    {
-        self.rt.block_on(f)
-    }
-    pub fn new(url: impl AsRef<str>) -> anyhow::Result<Store> {
-        let rt = Runtime::new()?;
-        let pool = rt.block_on(SqlitePool::connect(url.as_ref()))?;
-        rt.block_on(sqlx::migrate!().run(&pool))?;
-        Ok(Store { pool, rt })
-    }
-    fn add_base_config(
-        &self,
-        cfg_name: impl AsRef<str>,
-        cfg: serde_json::Value,
-    ) -> anyhow::Result<i64> {
-        let hash = calculate_cfg_hash(&cfg)?;
-        let hash_str = format!("{}", hash);
-        let name = cfg_name.as_ref();
-        let version = self.block_on(
-            sqlx::query_scalar!(
-                r#"
-            INSERT INTO BaseCfgs (name, cfg, version, cfg_hash) 
-            VALUES (
-            $1,
-            $2,
-            (SELECT COUNT(*) FROM BaseCfgs WHERE BaseCfgs.name = $1), $3
-            ) RETURNING version;"#,
-                name,
-                cfg,
-                hash_str
-            )
-            .fetch_one(&self.pool),
-        )?;
-        info!("Succesfully added base config");
-        self.block_on(
-            sqlx::query!(
-                r#"INSERT INTO Deltas (cfg_hash, delta) VALUES ($1, $2)"#,
-                hash_str,
-                Value::Null
-            )
-            .execute(&self.pool),
-        )?;
-        Ok(version)
-    }
-
-    pub fn get_base_config(
-        &self,
-        cfg_name: impl AsRef<str>,
-        version: Option<i64>,
-    ) -> anyhow::Result<Option<Value>> {
-        let ref_ = cfg_name.as_ref();
-        match version {
-            Some(v) => self
-                .block_on(
-                    sqlx::query_scalar!(
-                        r#"SELECT cfg as "cfg: Value" 
-                    FROM BaseCfgs
-                    WHERE name = $1 and version = $2;"#,
-                        ref_,
-                        v,
-                    )
-                    .fetch_optional(&self.pool),
-                )
-                .context(format!("Query fetching {}:{} failed", ref_, v)),
-            None => self
-                .block_on(
-                    sqlx::query_scalar!(
-                        r#"SELECT cfg as "cfg: Value"
-                    FROM BaseCfgs 
-                    WHERE name = $1 and version = (
-                    SELECT MAX(version) FROM BaseCfgs where name = $1
-                    );"#,
-                        ref_,
-                    )
-                    .fetch_optional(&self.pool),
-                )
-                .context(format!("Query fetching {}:latest failed", ref_)),
-        }
-    }
-    fn get_base_config_by_hash(&self, cfg_hash: u64) -> anyhow::Result<Option<Value>> {
-        let hash_str = format!("{}", cfg_hash);
-        let cfg = self
-            .block_on(
-                query_scalar!(
-                    r#"SELECT cfg as "cfg: Value" FROM BaseCfgs WHERE cfg_hash = $1 LIMIT 1;"#,
-                    hash_str
-                )
-                .fetch_optional(&self.pool),
-            )
-            .context("Fetching base config failed.")?;
-        Ok(cfg)
-    }
-    pub fn add_config(
-        &self,
-        cfg_name: impl AsRef<str>,
-        cfg: serde_json::Value,
-    ) -> anyhow::Result<()> {
-        let hash = calculate_cfg_hash(&cfg)?;
-        let hash_str = format!("{}", hash);
-        match self
-            .get_base_config_by_hash(hash)
-            .context("Couldn't hash config shape.")?
-        {
-            Some(base_cfg) => {
-                debug!("Base Config found for {}", cfg_name.as_ref());
-                let Some(delta) = calculate_delta(&base_cfg, &cfg) else {
-                    return Ok(());
-                };
-                debug!("Delta found {}", &delta);
-                self.block_on(
-                    sqlx::query!(
-                        "INSERT INTO Deltas (cfg_hash, delta) VALUES ($1, $2)",
-                        hash_str,
-                        delta
-                    )
-                    .execute(&self.pool),
-                )?;
-            }
-            None => {
-                debug!("No Base Config found for {}", cfg_name.as_ref());
-                self.add_base_config(cfg_name.as_ref(), cfg)?;
-            }
-        };
-        Ok(())
-    }
-    /// Fetch the config at a name and a version, if version is none, then the latest version.
-    /// Return's the most recent delta commited.
-    pub fn get_latest_config(
-        &self,
-        cfg_name: impl AsRef<str>,
-        version: Option<i64>,
-    ) -> anyhow::Result<Option<Value>> {
-        let name = cfg_name.as_ref();
-        let (hash, delta) = match version {
-            Some(v) => {
-                let Some(row) = self
-                    .block_on(
-                        sqlx::query_file!("sql/query_specific_version_cfg.sql", name, v)
-                            .fetch_optional(&self.pool),
-                    )
-                    .context("Failed to query latest config.")?
-                else {
-                    return Ok(None);
-                };
-                (row.cfg_hash.parse::<u64>().unwrap(), row.delta)
-            }
-            None => {
-                let Some(row) = self
-                    .block_on(
-                        sqlx::query_file!("sql/query_latest_version_cfg.sql", name)
-                            .fetch_optional(&self.pool),
-                    )
-                    .context("Failed to query latest config.")?
-                else {
-                    return Ok(None);
-                };
-                (row.cfg_hash.parse::<u64>().unwrap(), row.delta)
-            }
-        };
-        let base_cfg = self
-            .get_base_config_by_hash(hash)?
-            .ok_or(anyhow!("Expected to find a hash in the database."))?;
-        Ok(Some(build_cfg_from_base_and_delta(base_cfg, delta)))
-    }
+/// Version tag prefixed to every shape fingerprint, bump it whenever the
+/// encoding in `calculate_cfg_hash` changes so stored hashes get rewritten.
+const CFG_HASH_VERSION: &str = "v2";
+/// Keys of an element wise array delta, see `build_cfg_from_base_and_delta`.
+const ARRAY_LEN_KEY: &str = "$len";
+const ARRAY_SET_KEY: &str = "$set";
+const ARRAY_PATCH_KEY: &str = "$patch";
 
-    /// Get the base configuration types and the number of versions they have
-    pub fn get_base_configs(&self) -> anyhow::Result<Vec<(String, i64)>> {
-        let rows = self
-            .block_on(sqlx::query!(r#"SELECT name, version FROM BaseCfgs"#).fetch_all(&self.pool));
-        match rows {
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => Ok(vec![]),
-                _ => Err(e).context("An error occured during fetching configs."),
-            },
-            Ok(vec) => Ok(vec.into_iter().map(|r| (r.name, r.version)).collect()),
-        }
-    }
-    pub fn get_base_config_hash(
-        &self,
-        cfg_name: impl AsRef<str>,
-        version: Option<i64>,
-    ) -> anyhow::Result<u64> {
-        debug!(
-            "Querying base_cfg: {} ver {}",
-            cfg_name.as_ref(),
-            version
-                .map(|i| format!("{}", i))
-                .unwrap_or("latest".to_string()),
-        );
-        let cfg_name = cfg_name.as_ref();
-        let string_hash = match version {
-            Some(v) => self
-                .block_on(
-                    sqlx::query_scalar!(
-                        r#"SELECT cfg_hash FROM BaseCfgs WHERE name=$1 and version=$2;"#,
-                        cfg_name,
-                        v
-                    )
-                    .fetch_optional(&self.pool),
-                )?,
-            None => self.block_on(sqlx::query_scalar!(
-                r#"SELECT cfg_hash FROM BaseCfgs WHERE name=$1 and version=(SELECT MAX(version) from BaseCfgs WHERE name=$1);"#, cfg_name
-            ).fetch_optional(&self.pool))?,
-        };
-        match string_hash {
-            Some(h) => h.parse().context("Failed to parse u64 hash"),
-            None => {
-                let v = match version {
-                    Some(v) => format!("{}", v),
-                    None => String::from("latest"),
-                };
-                let all_bcfgs = self
-                    .get_base_configs()?
-                    .into_iter()
-                    .map(|(n, ver)| format!("{}:{}\n", n, ver))
-                    .reduce(|s, i| s + &i);
-                let msg = format!(
-                    "No config found with name {} and version {}\nAvailable Configs:\n{}",
-                    cfg_name,
-                    v,
-                    all_bcfgs.unwrap_or(String::from("No configs stored."))
-                );
-                Err(anyhow!(msg))
-            }
-        }
-    }
-    pub fn get_all_deltas(
-        &self,
-        cfg_name: impl AsRef<str>,
-        version: Option<u64>,
-    ) -> anyhow::Result<Vec<(i64, Value)>> {
-        let cfg_name = cfg_name.as_ref();
-        let base_config_hash = self
-            .get_base_config_hash(cfg_name, version.map(|i| i as i64))?
-            .to_string();
-        let query_result = self.block_on(
-            sqlx::query!(
-                r#"SELECT id, delta as "delta: Value" FROM Deltas WHERE cfg_hash = $1"#,
-                base_config_hash,
-            )
-            .fetch_all(&self.pool),
-        );
-        match query_result {
-            Err(e) => match e {
-                sqlx::Error::RowNotFound => {
-                    warn!(
-                        "No row was found cfg_name {}:{:?}, returning empty vec.",
-                        cfg_name, version,
-                    );
-                    Ok(vec![])
-                }
-                _ => Err(e).context("Querying deltas failed."),
-            },
-            Ok(vec) => Ok(vec.into_iter().map(|row| (row.id, row.delta)).collect()),
-        }
-    }
-    pub fn get_delta(&self, delta_id: i64) -> anyhow::Result<Value> {
-        let row = self.block_on(
-            sqlx::query!(
-                r#"SELECT Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value" FROM Deltas 
-                    INNER JOIN BaseCfgs on Deltas.cfg_hash = BaseCfgs.cfg_hash 
-                    WHERE id = $1"#,
-                delta_id,
-            )
-            .fetch_one(&self.pool),
-        )?;
-        let (delta, base) = (row.delta, row.cfg);
-        Ok(build_cfg_from_base_and_delta(base, delta))
-    }
+/// Read a config file into json with the built-in formats, see `FormatRegistry`.
+pub fn read_file(path: impl AsRef<Path>) -> Result<Value> {
+    FormatRegistry::default().read_file(path)
 }
+/// Apply a delta produced by `calculate_delta` onto its base config.
+///
+/// Objects are merged key by key, a key missing from the delta is unchanged and a
+/// `null` sets the value to null. The base marker row of a version is an empty object.
+///
+/// An object delta against an array is an element patch: `$len` is the new length,
+/// `$patch` maps indices to deltas of the base element and `$set` maps indices to
+/// whole replacement elements.
 pub fn build_cfg_from_base_and_delta(base_cfg: Value, delta: Value) -> Value {
     match (base_cfg, delta) {
         (Value::Object(mut base_obj), Value::Object(mut delta_obj)) => {
@@ -317,49 +48,97 @@ pub fn build_cfg_from_base_and_delta(base_cfg: Value, delta: Value) -> Value {
             }
             Value::Object(base_obj)
         }
-        (base_cfg, Value::Null) => base_cfg,
+        (Value::Array(base_arr), Value::Object(patch)) if patch.contains_key(ARRAY_LEN_KEY) => {
+            apply_array_patch(base_arr, patch)
+        }
         (_, delta) => delta,
     }
 }
+fn apply_array_patch(mut base_arr: Vec<Value>, mut patch: Map<String, Value>) -> Value {
+    let len = patch
+        .get(ARRAY_LEN_KEY)
+        .and_then(Value::as_u64)
+        .map_or(base_arr.len(), |l| l as usize);
+    base_arr.resize(len, Value::Null);
+    let indexed = |m: Option<Value>| match m {
+        Some(Value::Object(m)) => m
+            .into_iter()
+            .filter_map(|(i, v)| Some((i.parse::<usize>().ok()?, v)))
+            .filter(|(i, _)| *i < len)
+            .collect(),
+        _ => vec![],
+    };
+    for (i, delta) in indexed(patch.remove(ARRAY_PATCH_KEY)) {
+        base_arr[i] = build_cfg_from_base_and_delta(std::mem::take(&mut base_arr[i]), delta);
+    }
+    for (i, value) in indexed(patch.remove(ARRAY_SET_KEY)) {
+        base_arr[i] = value;
+    }
+    Value::Array(base_arr)
+}
 
-fn calculate_cfg_hash(json: &Value) -> anyhow::Result<u64> {
-    let mut hasher = DefaultHasher::new();
-    match json {
-        Value::Object(o) => generate_keys(o, vec![]).hash(&mut hasher),
-        _ => Err(anyhow!("Json object, should be a tree not a single leaf."))?,
+/// Fingerprint of a config's key structure, values don't contribute.
+///
+/// Each key path is encoded as a json array of its keys, eg. `["server","port"]`, where
+/// stepping into an array element is a `null` segment, eg. `["script",null,"name"]`.
+/// Array lengths don't contribute. The distinct encoded paths are sorted, joined with `\n`
+/// and hashed with SHA-256, the hex digest is prefixed with `CFG_HASH_VERSION`.
+/// This is stable across builds and toolchains.
+fn calculate_cfg_hash(json: &Value) -> Result<String> {
+    let Value::Object(_) = json else {
+        return Err(DeltaError::InvalidRootType);
     };
-    Ok(hasher.finish())
+    let mut paths = generate_keys(json, &mut vec![], vec![])
+        .iter()
+        .map(|p| serde_json::to_string(p).expect("Key paths always serialize."))
+        .collect::<Vec<String>>();
+    paths.sort_unstable();
+    paths.dedup();
+    let digest = Sha256::digest(paths.join("\n"));
+    Ok(format!("{}:{:x}", CFG_HASH_VERSION, digest))
 }
-fn generate_keys(json: &Map<String, Value>, mut keys: Vec<String>) -> Vec<String> {
-    for (k, v) in json {
-        keys.push(k.clone());
-        let Value::Object(o) = v else {
-            continue;
-        };
-        keys = generate_keys(o, keys);
+fn generate_keys(
+    json: &Value,
+    prefix: &mut Vec<Option<String>>,
+    mut keys: Vec<Vec<Option<String>>>,
+) -> Vec<Vec<Option<String>>> {
+    match json {
+        Value::Object(o) => {
+            for (k, v) in o {
+                prefix.push(Some(k.clone()));
+                keys.push(prefix.clone());
+                keys = generate_keys(v, prefix, keys);
+                prefix.pop();
+            }
+        }
+        Value::Array(a) => {
+            prefix.push(None);
+            keys.push(prefix.clone());
+            for v in a {
+                keys = generate_keys(v, prefix, keys);
+            }
+            prefix.pop();
+        }
+        _ => (),
     }
     keys
 }
-fn calculate_delta(base_json: &Value, comparison_json: &Value) -> Option<Value> {
-    assert_eq!(
-        calculate_cfg_hash(base_json).unwrap_or(0),
-        calculate_cfg_hash(comparison_json).unwrap_or(0),
-        "Key structure must be the same in the jsons."
-    );
-    match (base_json, comparison_json) {
+/// Delta from a base config to a config of the same shape, None if they are equal.
+fn calculate_delta(base_json: &Value, comparison_json: &Value) -> Result<Option<Value>> {
+    if calculate_cfg_hash(base_json)? != calculate_cfg_hash(comparison_json)? {
+        return Err(DeltaError::ShapeMismatch);
+    }
+    Ok(calculate_value_delta(base_json, comparison_json))
+}
+/// Delta of two values that share a key structure, None if they are equal.
+fn calculate_value_delta(base_value: &Value, comparison_value: &Value) -> Option<Value> {
+    match (base_value, comparison_value) {
         (Value::Object(base_object), Value::Object(comparsion_object)) => {
             let mut delta = Map::new();
             for (k, base_value) in base_object {
                 let comparison_value = comparsion_object.get(k).unwrap();
-                if let Some(r_delta) = match (base_value, comparison_value) {
-                    (Value::Object(_), Value::Object(_)) => {
-                        calculate_delta(base_value, comparison_value)
-                    }
-                    _ => None,
-                } {
+                if let Some(r_delta) = calculate_value_delta(base_value, comparison_value) {
                     delta.insert(k.clone(), r_delta);
-                } else if comparison_value != base_value {
-                    delta.insert(k.clone(), comparison_value.clone());
                 };
             }
             if !delta.is_empty() {
@@ -367,9 +146,64 @@ fn calculate_delta(base_json: &Value, comparison_json: &Value) -> Option<Value>
             }
             None
         }
+        (Value::Array(base_arr), Value::Array(comparison_arr)) => {
+            calculate_array_delta(base_arr, comparison_arr)
+        }
+        _ if base_value != comparison_value => Some(comparison_value.clone()),
         _ => None,
     }
 }
+/// Element wise delta of two arrays, see `build_cfg_from_base_and_delta` for the encoding.
+/// Falls back to the whole array when every element changed.
+fn calculate_array_delta(base_arr: &[Value], comparison_arr: &[Value]) -> Option<Value> {
+    if base_arr == comparison_arr {
+        return None;
+    }
+    let mut set = Map::new();
+    let mut patch = Map::new();
+    for (i, comparison_value) in comparison_arr.iter().enumerate() {
+        match base_arr.get(i) {
+            Some(base_value) if base_value == comparison_value => (),
+            Some(base_value) if same_structure(base_value, comparison_value) => {
+                if let Some(delta) = calculate_value_delta(base_value, comparison_value) {
+                    patch.insert(i.to_string(), delta);
+                }
+            }
+            _ => {
+                set.insert(i.to_string(), comparison_value.clone());
+            }
+        }
+    }
+    if set.len() + patch.len() >= comparison_arr.len() {
+        return Some(Value::Array(comparison_arr.to_vec()));
+    }
+    let mut delta = Map::new();
+    delta.insert(ARRAY_LEN_KEY.to_string(), Value::from(comparison_arr.len()));
+    if !set.is_empty() {
+        delta.insert(ARRAY_SET_KEY.to_string(), Value::Object(set));
+    }
+    if !patch.is_empty() {
+        delta.insert(ARRAY_PATCH_KEY.to_string(), Value::Object(patch));
+    }
+    Some(Value::Object(delta))
+}
+/// Whether a delta between the two values can be applied with `build_cfg_from_base_and_delta`.
+/// Array elements needn't share a structure, even when the arrays do.
+fn same_structure(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Object(_), Value::Object(_)) => {
+            let mut a_keys = generate_keys(a, &mut vec![], vec![]);
+            let mut b_keys = generate_keys(b, &mut vec![], vec![]);
+            a_keys.sort_unstable();
+            a_keys.dedup();
+            b_keys.sort_unstable();
+            b_keys.dedup();
+            a_keys == b_keys
+        }
+        (Value::Array(_), Value::Array(_)) => true,
+        _ => false,
+    }
+}
 
 #[cfg(test)]
 mod test_delta {
@@ -393,125 +227,128 @@ mod test_delta {
         assert_ne!(hash, hash_2);
     }
     #[test]
+    fn test_hash_differs_on_nesting() {
+        let json = json!({"test": {"deep": 0}});
+        let json_2 = json!({"test": 0, "deep": 0});
+        let hash = calculate_cfg_hash(&json).unwrap();
+        let hash_2 = calculate_cfg_hash(&json_2).unwrap();
+        assert_ne!(hash, hash_2);
+    }
+    #[test]
+    fn test_hash_is_stable() {
+        let json = json!({"test": {"really": {"super": 0}, "deep": 0}});
+        assert_eq!(
+            calculate_cfg_hash(&json).unwrap(),
+            "v2:ec5c3a704cef97b6c4f5b3139f32538b8f0090495ff3a7e713a11a54c0c00164"
+        );
+    }
+    #[test]
+    fn test_hash_differs_on_array_elements() {
+        let json = json!({"script": [{"name": "build"}, {"name": "test"}]});
+        let json_2 = json!({"script": [{"name": "build"}, {"run": "test"}]});
+        let hash = calculate_cfg_hash(&json).unwrap();
+        let hash_2 = calculate_cfg_hash(&json_2).unwrap();
+        assert_ne!(hash, hash_2);
+    }
+    #[test]
+    fn test_hash_array_length_invariant() {
+        let json = json!({"script": [{"name": "build"}], "tags": [1]});
+        let json_2 = json!({"script": [{"name": "build"}, {"name": "test"}], "tags": []});
+        let hash = calculate_cfg_hash(&json).unwrap();
+        let hash_2 = calculate_cfg_hash(&json_2).unwrap();
+        assert_eq!(hash, hash_2);
+    }
+    #[test]
     fn test_hash_fail_on_leaf() {
         let json = json!("string");
-        assert!(calculate_cfg_hash(&json).is_err());
+        assert!(matches!(
+            calculate_cfg_hash(&json),
+            Err(DeltaError::InvalidRootType)
+        ));
     }
     #[test]
     fn test_calculate_delta() {
         let json = json!({"test": {"really": {"super": 0}, "deep": 0}});
         let json_2 = json!({"test": {"really": {"super": 1}, "deep": 1}});
-        assert!(calculate_delta(&json, &json_2).is_some())
+        assert!(calculate_delta(&json, &json_2).unwrap().is_some())
     }
     #[test]
     fn test_calculate_delta_none() {
         let json = json!({"test": {"really": {"super": 0}, "deep": 0}});
-        assert!(calculate_delta(&json, &json).is_none())
+        assert!(calculate_delta(&json, &json).unwrap().is_none())
     }
     #[test]
     fn test_calculate_delta_structure() {
         let json = json!({"test": {"really": {"super": 0}, "deep": 0}});
         let json_2 = json!({"test": {"really": {"super": 1}, "deep": 1}});
-        assert_eq!(calculate_delta(&json, &json_2).unwrap(), json_2)
+        assert_eq!(calculate_delta(&json, &json_2).unwrap().unwrap(), json_2)
     }
     #[test]
     fn test_calculate_delta_structure_drops_consistent_keys() {
         let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
         let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
         let delta = json!({"test": {"really": {"super": 1}, "deep": 1}});
-        assert_eq!(calculate_delta(&json, &json_2).unwrap(), delta);
+        assert_eq!(calculate_delta(&json, &json_2).unwrap().unwrap(), delta);
     }
     #[test]
-    fn test_delta_reconstruction() {
-        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
-        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
-        let delta = calculate_delta(&json, &json_2).unwrap();
-        assert_eq!(json_2, build_cfg_from_base_and_delta(json, delta))
-    }
-}
-
-#[cfg(test)]
-mod db_tests {
-
-    use super::*;
-    use serde_json::json;
-    fn mock_db() -> Store {
-        let s = Store::new("sqlite::memory:").unwrap();
-        s.block_on(sqlx::migrate!().run(&s.pool)).unwrap();
-        s
+    fn test_calculate_delta_shape_mismatch() {
+        let json = json!({"test": {"really": 0}});
+        let json_2 = json!({"test": {"deep": 0}});
+        assert!(matches!(
+            calculate_delta(&json, &json_2),
+            Err(DeltaError::ShapeMismatch)
+        ));
     }
     #[test]
-    fn test_add() {
-        let s = mock_db();
-        let json = serde_json::json!({"test": 200});
-        s.add_base_config("Test".to_owned(), json).unwrap();
+    fn test_delta_to_null() {
+        let json = json!({"test": {"really": 5, "deep": [0, 1]}});
+        let json_2 = json!({"test": {"really": null, "deep": [0, null]}});
+        let delta = calculate_delta(&json, &json_2).unwrap().unwrap();
+        assert_eq!(json_2, build_cfg_from_base_and_delta(json.clone(), delta));
+        let delta = calculate_delta(&json_2, &json).unwrap().unwrap();
+        assert_eq!(json, build_cfg_from_base_and_delta(json_2, delta));
     }
     #[test]
-    fn test_add_get() {
-        let s = mock_db();
-        let json = serde_json::json!({"test": 200});
-        s.add_base_config("Test".to_owned(), json.clone()).unwrap();
-        let cfg = s.get_base_config("Test", None).unwrap().unwrap();
-        assert_eq!(cfg, json)
-    }
-    #[test]
-    fn test_add_multi() {
-        let s = mock_db();
-        let mut c: i64 = -1;
-        let mut id = 0;
-        for i in 0..10 {
-            let json = serde_json::json!({format!("test{}",i): 200});
-            c = s.add_base_config("test".to_owned(), json.clone()).unwrap();
-            id = i;
-        }
-        assert_eq!(c, id);
-    }
-    #[test]
-    fn test_read_latest() {
-        let s = mock_db();
-        let json = serde_json::json!({format!("test"): 200});
-        s.add_base_config("test".to_owned(), json.clone()).unwrap();
-        let json2 = serde_json::json!({format!("test"): 200, format!("Test"): 200});
-        s.add_base_config("test".to_owned(), json2.clone()).unwrap();
-        let c = s.get_latest_config("test", None).unwrap().unwrap();
-        assert_eq!(c, json2);
+    fn test_base_marker_reconstruction() {
+        let json = json!({"test": {"really": 5, "deep": null}});
+        assert_eq!(
+            json.clone(),
+            build_cfg_from_base_and_delta(json, Value::Object(Map::new()))
+        );
     }
     #[test]
-    fn test_insert_read() {
-        let db = mock_db();
-        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
-        db.add_config("test_ins", json.clone()).unwrap();
-        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
-        assert_eq!(json, json_out);
+    fn test_array_delta_is_element_wise() {
+        let items = (0..500).map(|i| json!({"id": i})).collect::<Vec<Value>>();
+        let mut items_2 = items.clone();
+        items_2[3] = json!({"id": -1});
+        let json = json!({"items": items});
+        let json_2 = json!({"items": items_2});
+        let delta = calculate_delta(&json, &json_2).unwrap().unwrap();
+        assert_eq!(
+            delta,
+            json!({"items": {"$len": 500, "$patch": {"3": {"id": -1}}}})
+        );
+        assert_eq!(json_2, build_cfg_from_base_and_delta(json, delta));
     }
     #[test]
-    fn test_insert_read_version() {
-        let db = mock_db();
-        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
-        let json_2 = json!({"tester": {"really": {"super": 0, "duper": 0}, "deep": 0}});
-        db.add_config("test_ins", json.clone()).unwrap();
-        db.add_config("test_ins", json_2.clone()).unwrap();
-        let json_out = db.get_latest_config("test_ins", Some(1)).unwrap().unwrap();
-        assert_eq!(json_2, json_out);
+    fn test_array_delta_reconstruction() {
+        let json = json!({"s": [{"a": 0}, {"b": 0}, [1, 2], 3], "t": [1, 2, 3, 4]});
+        let cases = [
+            json!({"s": [{"a": 1}, {"b": 0}, [1, 2], 3], "t": [1, 2, 3, 4]}),
+            json!({"s": [{"b": 0}, {"a": 0}, [1, 2], 3], "t": [1, 2]}),
+            json!({"s": [{"a": 0}, {"b": 0}, [2], 3, {"a": 4}], "t": [1, 2, 3, 4, 5]}),
+            json!({"s": [[1], {"b": 1}, 3, {"a": 0}], "t": [4, 3, 2, 1]}),
+        ];
+        for json_2 in cases {
+            let delta = calculate_delta(&json, &json_2).unwrap().unwrap();
+            assert_eq!(json_2, build_cfg_from_base_and_delta(json.clone(), delta));
+        }
     }
     #[test]
-    fn test_read_from_delta() {
-        let db = mock_db();
+    fn test_delta_reconstruction() {
         let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
         let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
-        db.add_config("test_ins", json.clone()).unwrap();
-        db.add_config("test_ins", json_2.clone()).unwrap();
-        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
-        assert_eq!(json_2, json_out);
-    }
-    #[test]
-    fn test_read_all() {
-        let db = mock_db();
-        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
-        let json_2 = json!({"tester": {"really": {"super": 1, "duper": 0}, "deep": 1}});
-        db.add_config("test_ins", json.clone()).unwrap();
-        db.add_config("test_ins", json_2.clone()).unwrap();
-        let cfgs = db.get_base_configs().unwrap();
-        assert_eq!(cfgs.len(), 2)
+        let delta = calculate_delta(&json, &json_2).unwrap().unwrap();
+        assert_eq!(json_2, build_cfg_from_base_and_delta(json, delta))
     }
 }
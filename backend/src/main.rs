@@ -1,15 +1,31 @@
 use anyhow::anyhow;
-use clap::{Parser, Subcommand};
-use delta_backend::{build_cfg_from_base_and_delta, read_file, Store};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use clap::{Args, Parser, Subcommand};
+use delta_backend::{
+    build_cfg_from_base_and_delta, diff_configs, expand_paths, name_collisions, watch_roots,
+    AddOutcome, Change, ConfigFormat, DeltaError, FormatRegistry, HistoryEntry, Naming, PathFilter,
+    RetentionPolicy, Store,
+};
 use delta_tui::{self, base_searcher::BaseSearch, App};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebouncedEventKind};
 use serde_json::Value;
 use std::{
+    collections::HashSet,
+    convert::Infallible,
     env,
+    fmt::{self, Display, Formatter},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
-    process::exit,
+    process::{exit, Command},
+    str::FromStr,
+    sync::mpsc,
+    time::Duration,
 };
 use tracing::debug;
 
+/// Path argument read from stdin instead of a file.
+const STDIN_PATH: &str = "-";
+
 fn main() {
     tracing_subscriber::fmt::init();
     let args = Cli::parse();
@@ -18,8 +34,9 @@ fn main() {
     match run(args, &url) {
         Ok(()) => exit(0),
         Err(e) => {
+            let code = exit_code(&e);
             pretty_error_print(e);
-            exit(1);
+            exit(code);
         }
     };
 }
@@ -31,6 +48,24 @@ fn pretty_error_print(e: anyhow::Error) {
         eprintln!("This was caused by an inital error:\n{}", e.root_cause());
     }
 }
+/// Map an error to the process exit code, these are listed in the cli help.
+fn exit_code(e: &anyhow::Error) -> i32 {
+    match e.downcast_ref::<DeltaError>() {
+        Some(DeltaError::ConfigNotFound { .. } | DeltaError::DeltaNotFound(_)) => 2,
+        Some(
+            DeltaError::InvalidPath(_)
+            | DeltaError::InvalidPattern { .. }
+            | DeltaError::InvalidQuery(_)
+            | DeltaError::InvalidPolicy(_)
+            | DeltaError::UnsupportedFormat(_)
+            | DeltaError::Parse { .. }
+            | DeltaError::Render { .. },
+        ) => 3,
+        Some(DeltaError::ShapeMismatch | DeltaError::InvalidRootType) => 4,
+        Some(DeltaError::Database(_) | DeltaError::Migration(_)) => 5,
+        Some(DeltaError::Io(_)) | None => 1,
+    }
+}
 fn construct_db_url() -> String {
     let path = match env::var("DELTA_DB_PATH") {
         Ok(p) => p,
@@ -60,6 +95,7 @@ fn construct_db_url() -> String {
 }
 fn run(args: Cli, url: &str) -> anyhow::Result<()> {
     let s = Store::new(url)?;
+    let formats = FormatRegistry::default();
     let Some(mode) = args.mode else {
         let cfgs = s.get_base_configs()?;
         let mut joint = vec![];
@@ -93,10 +129,33 @@ fn run(args: Cli, url: &str) -> anyhow::Result<()> {
         return Ok(());
     };
     match mode {
-        Modes::Get { delta_id } => {
-            debug!("Mode get on {}", &delta_id);
-            let config = s.get_delta(delta_id)?;
-            println!("{}", serde_json::to_string(&config)?)
+        Modes::Get {
+            target,
+            format,
+            pretty,
+            at,
+        } => {
+            debug!("Mode get on {:?}", &target);
+            let config = match (at, target) {
+                (None, target) => target.resolve(&s)?.1,
+                (
+                    Some(at),
+                    ConfigRef::Named {
+                        name,
+                        version: None,
+                    },
+                ) => s
+                    .get_config_at(&name, at)?
+                    .ok_or_else(|| anyhow!("{} had no stored config at {}", name, at))?,
+                (Some(_), _) => {
+                    return Err(anyhow!("--at takes a config name without a version."));
+                }
+            };
+            let rendered = match pretty {
+                true => formats.render_pretty(&format, &config)?,
+                false => formats.render(&format, &config)?,
+            };
+            println!("{}", rendered.trim_end())
         }
         Modes::List => {
             debug!("Mode list.");
@@ -164,11 +223,19 @@ fn run(args: Cli, url: &str) -> anyhow::Result<()> {
                 }
             }
         }
-        Modes::Add { paths } => {
+        Modes::Add {
+            paths,
+            atomic: true,
+            source,
+            filter,
+        } => {
+            let paths = expand_paths(&paths, &filter.into(), &formats)?;
+            source.check(&paths)?;
+            let mut cfgs = vec![];
             let mut failure = false;
-            for path in paths {
-                match print_addition_result(&s, path.clone()) {
-                    Ok(()) => (),
+            for path in &paths {
+                match read_named_configs(&formats, &source, path) {
+                    Ok(named) => cfgs.extend(named),
                     Err(e) => {
                         eprintln!("{} Failed due to {}", path.display(), e);
                         failure = true
@@ -176,40 +243,743 @@ fn run(args: Cli, url: &str) -> anyhow::Result<()> {
                 }
             }
             if failure {
+                eprintln!("Nothing was added.");
+                exit(1);
+            }
+            let names = cfgs.iter().map(|(n, _)| n.clone()).collect::<Vec<String>>();
+            let outcomes = s.add_configs(cfgs)?;
+            let checked = canonical_paths(&paths);
+            for path in &paths {
+                record_source(&s, &source, path, &checked)?;
+            }
+            let mut summary = AddSummary::default();
+            for (name, outcome) in names.iter().zip(outcomes) {
+                summary.record(&outcome);
+                print_outcome(name, outcome);
+            }
+            println!("{}", summary);
+        }
+        Modes::Add {
+            paths,
+            atomic: false,
+            source,
+            filter,
+        } => {
+            let paths = expand_paths(&paths, &filter.into(), &formats)?;
+            source.check(&paths)?;
+            let mut summary = AddSummary::default();
+            let checked = canonical_paths(&paths);
+            for path in paths {
+                let added = print_addition_result(
+                    &s,
+                    &formats,
+                    &source,
+                    path.clone(),
+                    &checked,
+                    &mut summary,
+                );
+                match added {
+                    Ok(()) => (),
+                    Err(e) => {
+                        eprintln!("{} Failed due to {}", path.display(), e);
+                        summary.failed += 1;
+                    }
+                }
+            }
+            println!("{}", summary);
+            if summary.failed > 0 {
                 exit(1);
             }
         }
+        Modes::Watch {
+            paths,
+            debounce,
+            source,
+            filter,
+        } => {
+            if paths.iter().any(|p| p == Path::new(STDIN_PATH)) {
+                return Err(anyhow!("stdin can't be watched."));
+            }
+            let debounce = Duration::from_millis(debounce);
+            watch(&s, &formats, &paths, &source, &filter.into(), debounce)?;
+        }
+        Modes::Checkout {
+            target,
+            output,
+            format,
+            force,
+        } => {
+            let (name, config) = target.resolve(&s)?;
+            let path = match output {
+                Some(path) => path,
+                None if name.contains('#') => {
+                    return Err(anyhow!(
+                        "{} is one document of a multi document file, pass -o to choose where to write it.",
+                        name
+                    ));
+                }
+                None => PathBuf::from(&name),
+            };
+            let format = match format {
+                Some(f) => f,
+                None => formats
+                    .by_path(&path)
+                    .or_else(|| format_of_name(&formats, &name))
+                    .ok_or_else(|| DeltaError::UnsupportedFormat(name.clone()))?
+                    .name()
+                    .to_string(),
+            };
+            if path.exists() && !force {
+                let on_disk = formats.read_file(&path).ok();
+                if on_disk.as_ref() == Some(&config) {
+                    println!("{} already matches {}", path.display(), name);
+                    return Ok(());
+                }
+                let stored = match on_disk {
+                    Some(cfg) => match s.contains_config(&name, &cfg) {
+                        // Only objects are stored, so a file holding anything else isn't.
+                        Err(DeltaError::InvalidRootType) => false,
+                        stored => stored?,
+                    },
+                    None => false,
+                };
+                if !stored {
+                    return Err(anyhow!(
+                        "{} has changes that aren't stored, add it first or pass --force to overwrite them.",
+                        path.display()
+                    ));
+                }
+            }
+            let mut rendered = formats.render_pretty(&format, &config)?;
+            if !rendered.ends_with('\n') {
+                rendered.push('\n');
+            }
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, rendered)?;
+            println!("Checked out {} to {}", name, path.display());
+        }
+        Modes::Diff { old, new, json } => {
+            let old = load_config_or_file(&s, &formats, &old)?;
+            let new = load_config_or_file(&s, &formats, &new)?;
+            let changes = diff_configs(&old, &new);
+            match json {
+                true => println!("{}", serde_json::to_string_pretty(&changes)?),
+                false => changes.iter().for_each(|c| println!("{}", c)),
+            }
+        }
+        Modes::Log { name, all_versions } => {
+            let history = s.get_history(&name)?;
+            let latest = history.iter().map(|e| e.version).max().unwrap_or_default();
+            let mut previous: Option<&Value> = None;
+            for entry in &history {
+                let summary = match previous {
+                    None => String::from("initial config"),
+                    Some(prev) => change_summary(&diff_configs(prev, &entry.cfg)),
+                };
+                previous = Some(&entry.cfg);
+                if !all_versions && entry.version != latest {
+                    continue;
+                }
+                println!("{}  {}", entry_line(entry), summary);
+            }
+        }
+        Modes::Query { name, predicate } => {
+            for entry in s.query(&name, &predicate)? {
+                println!("{}", entry_line(&entry).trim_end());
+            }
+        }
+        Modes::Grep { pattern } => {
+            for hit in s.search_text(&pattern)? {
+                println!(
+                    "{:>6}  {}@{}  {} = {}",
+                    hit.id, hit.name, hit.version, hit.path, hit.value
+                );
+            }
+        }
+        Modes::Rm {
+            target,
+            dry_run,
+            yes,
+        } => {
+            let (name, history) = match &target {
+                ConfigRef::Delta(id) => {
+                    let (name, _) = s.get_delta_origin(*id)?;
+                    let history = s.get_history(&name)?;
+                    (name, history.into_iter().filter(|e| e.id == *id).collect())
+                }
+                ConfigRef::Named { name, version } => {
+                    let history = s.get_history(name)?;
+                    let removed = history
+                        .into_iter()
+                        .filter(|e| version.is_none_or(|v| e.version == v))
+                        .collect::<Vec<_>>();
+                    if removed.is_empty() {
+                        return Err(DeltaError::ConfigNotFound {
+                            name: name.clone(),
+                            version: *version,
+                            available: s.get_base_configs()?,
+                        }
+                        .into());
+                    }
+                    (name.clone(), removed)
+                }
+            };
+            for entry in &history {
+                println!("{}", entry_line(entry).trim_end());
+            }
+            let what = match &target {
+                ConfigRef::Delta(id) => format!("delta {} of {}", id, name),
+                ConfigRef::Named {
+                    version: Some(v), ..
+                } => format!("{} configs of {}@{}", history.len(), name, v),
+                ConfigRef::Named { version: None, .. } => {
+                    format!("{} configs of {} across all versions", history.len(), name)
+                }
+            };
+            if dry_run {
+                println!("Would remove {}.", what);
+                return Ok(());
+            }
+            if !yes && !confirm(&format!("Remove {}?", what))? {
+                println!("Nothing removed.");
+                return Ok(());
+            }
+            match target {
+                ConfigRef::Delta(id) => s.delete_delta(id)?,
+                ConfigRef::Named {
+                    version: Some(v), ..
+                } => {
+                    s.delete_base_version(&name, v)?;
+                }
+                ConfigRef::Named { version: None, .. } => {
+                    s.delete_config_family(&name)?;
+                }
+            }
+            println!("Removed {}.", what);
+        }
+        Modes::Tag { id, tags, remove } => {
+            for tag in &tags {
+                match remove {
+                    true if !s.untag_delta(id, tag)? => println!("Delta {} has no tag {}", id, tag),
+                    true => println!("Removed tag {} from delta {}", tag, id),
+                    false => s.tag_delta(id, tag)?,
+                }
+            }
+            if tags.is_empty() || !remove {
+                println!("{}", s.get_tags(id)?.join(", "));
+            }
+        }
+        Modes::Retention {
+            name,
+            keep_last,
+            keep_days,
+            keep_tagged,
+            clear,
+        } => {
+            if clear {
+                match s.clear_retention(&name)? {
+                    true => println!("Removed the retention policy of {}", name),
+                    false => println!("{} has no retention policy", name),
+                }
+            } else if keep_last.is_some() || keep_days.is_some() || keep_tagged {
+                let policy = RetentionPolicy {
+                    keep_last,
+                    keep_days,
+                    keep_tagged,
+                };
+                s.set_retention(&name, &policy)?;
+                println!("{}: {}", name, policy);
+            } else {
+                let policies = s.get_retention_policies()?;
+                match policies.iter().find(|(n, _)| *n == name) {
+                    Some((_, policy)) => println!("{}: {}", name, policy),
+                    None => println!("{} has no retention policy, gc keeps all of it", name),
+                }
+            }
+        }
+        Modes::Gc => {
+            let report = s.gc()?;
+            println!(
+                "Removed {} expired and {} duplicate deltas, reclaimed {} ({} -> {})",
+                report.expired,
+                report.duplicates,
+                fmt_size(report.reclaimed()),
+                fmt_size(report.size_before),
+                fmt_size(report.size_after)
+            );
+        }
+        Modes::Blame { name, version } => {
+            for entry in s.blame(&name, version)? {
+                println!(
+                    "{:>6}  {}  {} = {}",
+                    entry.id, entry.created_at, entry.path, entry.value
+                );
+            }
+        }
+        Modes::Bisect {
+            name,
+            good,
+            bad,
+            cmd,
+        } => {
+            let history = s.get_history(&name)?;
+            let position = |id: i64| {
+                history
+                    .iter()
+                    .position(|e| e.id == id)
+                    .ok_or_else(|| anyhow!("Delta {} isn't a config of {}.", id, name))
+            };
+            let (good, bad) = (position(good)?, position(bad)?);
+            if good >= bad {
+                return Err(anyhow!("The good delta must be older than the bad delta."));
+            }
+            let first_bad = bisect(&history[good..=bad], |entry| {
+                let passed = run_on_config(&formats, &name, entry, &cmd)?;
+                println!(
+                    "Delta {} is {}",
+                    entry.id,
+                    if passed { "good" } else { "bad" }
+                );
+                Ok(passed)
+            })?;
+            let entry = &history[good + first_bad];
+            let changes = diff_configs(&history[good + first_bad - 1].cfg, &entry.cfg);
+            println!(
+                "First bad delta is {} added {}, {}",
+                entry.id,
+                entry.created_at,
+                change_summary(&changes)
+            );
+        }
     };
     Ok(())
 }
-fn print_addition_result(s: &Store, path: PathBuf) -> anyhow::Result<()> {
-    if !path.exists() {
-        return Err(anyhow!("{} doesn't exist!", path.display()));
+/// Index of the first bad entry, where the first entry is known good and the
+/// last known bad. Entries are assumed good up to some point and bad after it.
+fn bisect<T>(
+    entries: &[T],
+    mut is_good: impl FnMut(&T) -> anyhow::Result<bool>,
+) -> anyhow::Result<usize> {
+    let (mut good, mut bad) = (0, entries.len() - 1);
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        match is_good(&entries[mid])? {
+            true => good = mid,
+            false => bad = mid,
+        }
+    }
+    Ok(bad)
+}
+/// Run a command against a config written to a temporary file, whose path is
+/// in `$DELTA_CONFIG` and whose delta id is in `$DELTA_ID`. Whether it succeeded.
+fn run_on_config(
+    formats: &FormatRegistry,
+    name: &str,
+    entry: &HistoryEntry,
+    cmd: &[String],
+) -> anyhow::Result<bool> {
+    let format = format_of_name(formats, name).map_or("json", |f| f.name());
+    let extension = formats
+        .by_name(format)?
+        .extensions()
+        .first()
+        .map_or(String::new(), |e| format!(".{}", e));
+    let mut file = tempfile::Builder::new()
+        .prefix("delta-bisect-")
+        .suffix(&extension)
+        .tempfile()?;
+    file.write_all(formats.render_pretty(format, &entry.cfg)?.as_bytes())?;
+    file.flush()?;
+    let status = Command::new(&cmd[0])
+        .args(&cmd[1..])
+        .env("DELTA_CONFIG", file.path())
+        .env("DELTA_ID", entry.id.to_string())
+        .status()
+        .map_err(|e| anyhow!("Running {} failed: {}", cmd[0], e))?;
+    Ok(status.success())
+}
+/// The format of a stored config's file, ignoring the `#<document>` suffix of
+/// configs read from a multi document file.
+fn format_of_name<'a>(formats: &'a FormatRegistry, name: &str) -> Option<&'a dyn ConfigFormat> {
+    formats.by_path(Path::new(name)).or_else(|| {
+        let (file, _) = name.split_once('#')?;
+        formats.by_path(Path::new(file))
+    })
+}
+/// The id, time, version and kind of a stored config, as listed by `log`.
+fn entry_line(entry: &HistoryEntry) -> String {
+    format!(
+        "{:>6}  {}  v{:<3} {:<5}",
+        entry.id,
+        entry.created_at,
+        entry.version,
+        if entry.is_base { "base" } else { "delta" },
+    )
+}
+/// A size in bytes with a binary unit, eg. `1.5 MiB`.
+fn fmt_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
     }
-    if !path.is_file() {
-        return Err(anyhow!("{} isn't a file!", path.display()));
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
     }
-    let Some(name) = fname_to_cfg_name(&path) else {
-        return Err(anyhow!(
-            "Expected a valid path to a config file got: {}",
-            &path.display()
-        ));
+}
+/// Ask a yes or no question on stderr, anything but y or yes is no.
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+/// Counts of added, removed and changed keys followed by the first few key paths.
+fn change_summary(changes: &[Change]) -> String {
+    const SHOWN_PATHS: usize = 4;
+    if changes.is_empty() {
+        return String::from("no changes");
+    }
+    let count = |f: fn(&Change) -> bool| changes.iter().filter(|c| f(c)).count();
+    let added = count(|c| matches!(c, Change::Added { .. }));
+    let removed = count(|c| matches!(c, Change::Removed { .. }));
+    let changed = count(|c| matches!(c, Change::Changed { .. }));
+    let mut paths = changes
+        .iter()
+        .take(SHOWN_PATHS)
+        .map(Change::path)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if changes.len() > SHOWN_PATHS {
+        paths.push_str(&format!(", {} more", changes.len() - SHOWN_PATHS));
+    }
+    format!("+{} -{} ~{}  {}", added, removed, changed, paths)
+}
+/// A config file on disk if `arg` is a path to one, otherwise a stored config.
+fn load_config_or_file(s: &Store, formats: &FormatRegistry, arg: &str) -> anyhow::Result<Value> {
+    if Path::new(arg).is_file() {
+        return Ok(formats.read_file(arg)?);
+    }
+    let Ok(target) = arg.parse::<ConfigRef>();
+    Ok(target.resolve(s)?.1)
+}
+/// Add every config found in `inputs`, then add them again whenever they're
+/// saved until interrupted. Failures are reported without stopping the watch.
+fn watch(
+    s: &Store,
+    formats: &FormatRegistry,
+    inputs: &[PathBuf],
+    source: &SourceArgs,
+    filter: &PathFilter,
+    debounce: Duration,
+) -> anyhow::Result<()> {
+    let tracked = expand_paths(inputs, filter, formats)?;
+    source.check(&tracked)?;
+    let checked = canonical_paths(&tracked);
+    let add = |path: PathBuf| {
+        let mut summary = AddSummary::default();
+        let added = print_addition_result(s, formats, source, path.clone(), &checked, &mut summary);
+        if let Err(e) = added {
+            eprintln!("{} Failed due to {}", path.display(), e);
+        }
     };
-    let c = read_file(&path)?;
-    s.add_config(&name, c)?;
-    println!("Successuflly added config {}", name);
+    tracked.into_iter().for_each(add);
+
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(debounce, tx)?;
+    for (root, recursive) in watch_roots(inputs) {
+        let mode = match recursive {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        };
+        debouncer.watcher().watch(&root, mode)?;
+    }
+    println!("Watching for changes, press Ctrl-C to stop.");
+    for events in rx {
+        let events = match events {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("Watching failed due to {}", e);
+                continue;
+            }
+        };
+        let changed = events
+            .into_iter()
+            // Continuous events fire while a file is still being written.
+            .filter(|e| e.kind == DebouncedEventKind::Any)
+            .filter_map(|e| e.path.canonicalize().ok())
+            .collect::<HashSet<_>>();
+        // Rescan so new files and .deltaignore edits are picked up.
+        let paths = match expand_paths(inputs, filter, formats) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("Rescanning failed due to {}", e);
+                continue;
+            }
+        };
+        paths
+            .into_iter()
+            .filter(|p| p.canonicalize().is_ok_and(|p| changed.contains(&p)))
+            .for_each(add);
+    }
+    Ok(())
+}
+fn print_addition_result(
+    s: &Store,
+    formats: &FormatRegistry,
+    source: &SourceArgs,
+    path: PathBuf,
+    checked: &HashSet<PathBuf>,
+    summary: &mut AddSummary,
+) -> anyhow::Result<()> {
+    for (name, c) in read_named_configs(formats, source, &path)? {
+        let outcome = s.add_config(&name, c)?;
+        summary.record(&outcome);
+        print_outcome(&name, outcome);
+    }
+    record_source(s, source, &path, checked)
+}
+/// The resolved paths of files added in one run, see `record_source`.
+fn canonical_paths(paths: &[PathBuf]) -> HashSet<PathBuf> {
+    paths.iter().filter_map(|p| p.canonicalize().ok()).collect()
+}
+/// Remember the file a config name is added from, warning when an earlier run
+/// added another file under that name. Files of this run, `checked`, that share a
+/// name were already reported by `SourceArgs::check`.
+fn record_source(
+    s: &Store,
+    source: &SourceArgs,
+    path: &Path,
+    checked: &HashSet<PathBuf>,
+) -> anyhow::Result<()> {
+    if path == Path::new(STDIN_PATH) {
+        return Ok(());
+    }
+    let naming = source.naming();
+    let name = naming.name(path)?;
+    match s.set_source(&name, path)? {
+        Some(previous) if !checked.contains(&previous) => {
+            eprintln!(
+                "Warning: {} is stored as config {}, which was added from {} before.",
+                path.display(),
+                name,
+                previous.display()
+            );
+            if matches!(naming, Naming::Basename) {
+                eprintln!("Use --root to name configs by their relative path instead.");
+            }
+        }
+        _ => (),
+    }
     Ok(())
 }
-fn fname_to_cfg_name(p: impl AsRef<Path>) -> Option<String> {
-    Some(p.as_ref().file_name()?.to_str()?.to_string())
+/// Totals of an add, printed after the line for each config.
+#[derive(Debug, Default)]
+struct AddSummary {
+    new_versions: usize,
+    new_deltas: usize,
+    unchanged: usize,
+    /// Files that couldn't be read or added.
+    failed: usize,
+}
+impl AddSummary {
+    fn record(&mut self, outcome: &AddOutcome) {
+        match outcome {
+            AddOutcome::NewBase { .. } => self.new_versions += 1,
+            AddOutcome::NewDelta { .. } => self.new_deltas += 1,
+            AddOutcome::Unchanged { .. } => self.unchanged += 1,
+        }
+    }
+}
+impl Display for AddSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} new versions, {} new deltas, {} unchanged",
+            self.new_versions, self.new_deltas, self.unchanged
+        )?;
+        if self.failed > 0 {
+            write!(f, ", {} failed", self.failed)?;
+        }
+        Ok(())
+    }
+}
+fn print_outcome(name: &str, outcome: AddOutcome) {
+    match outcome {
+        AddOutcome::NewBase { version } => {
+            println!("Added config {} as new base version {}", name, version)
+        }
+        AddOutcome::NewDelta { id } => println!("Added config {} as delta {}", name, id),
+        AddOutcome::Unchanged { existing_id } => {
+            println!("Config {} is unchanged from delta {}", name, existing_id)
+        }
+    }
+}
+/// Read every config in a file, or stdin for `-`, along with the names they're stored under.
+///
+/// A file holding several documents stores each one as `<name>#<index>`, or as
+/// `<name>#<value>` with the value at `--doc-key` of every document. A file holding
+/// one document is stored under `<name>`, so a file growing a second document
+/// starts new families. A file without any document is an error.
+fn read_named_configs(
+    formats: &FormatRegistry,
+    source: &SourceArgs,
+    path: &Path,
+) -> anyhow::Result<Vec<(String, Value)>> {
+    let (name, docs) = if path == Path::new(STDIN_PATH) {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        let format = match &source.format {
+            Some(f) => formats.by_name(f)?,
+            None => formats
+                .sniff(&contents)
+                .ok_or_else(|| DeltaError::UnsupportedFormat(String::from("stdin")))?,
+        };
+        let name = source.name.clone().expect("checked by SourceArgs::check");
+        (name, formats.parse_documents(format, &contents)?)
+    } else {
+        if !path.exists() {
+            return Err(anyhow!("{} doesn't exist!", path.display()));
+        }
+        if !path.is_file() {
+            return Err(anyhow!("{} isn't a file!", path.display()));
+        }
+        let name = source.naming().name(path)?;
+        (name, formats.read_documents(path)?)
+    };
+    if docs.is_empty() {
+        let from = match path == Path::new(STDIN_PATH) {
+            true => String::from("stdin"),
+            false => path.display().to_string(),
+        };
+        return Err(anyhow!("{} contains no documents!", from));
+    }
+    if docs.len() == 1 {
+        return Ok(vec![(name, docs.into_iter().next().unwrap())]);
+    }
+    docs.into_iter()
+        .enumerate()
+        .map(|(idx, doc)| {
+            let suffix = match &source.doc_key {
+                None => idx.to_string(),
+                Some(key) => doc_key_value(&doc, key).ok_or_else(|| {
+                    anyhow!("Document {} has no string or number at {}", idx, key)
+                })?,
+            };
+            Ok((format!("{}#{}", name, suffix), doc))
+        })
+        .collect()
+}
+/// The value at a dotted key path of a document, eg. `metadata.name`.
+fn doc_key_value(doc: &Value, key: &str) -> Option<String> {
+    let pointer = key.split('.').fold(String::new(), |p, k| p + "/" + k);
+    match doc.pointer(&pointer)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
 }
 
 #[derive(Parser, Debug)]
+#[command(after_help = "Exit codes:
+  1  Any other failure
+  2  Config name, version or delta id not found
+  3  Config path, pattern, query, retention policy or file format invalid or unparseable
+  4  Config isn't an object or has a mismatched shape
+  5  Database failure")]
 struct Cli {
     #[command(subcommand)]
     mode: Option<Modes>,
 }
 
+/// Where configs come from beyond their file name and extension.
+#[derive(Debug, Clone, Args)]
+struct SourceArgs {
+    /// Store every config under this name, required when reading stdin with `-`.
+    #[arg(long, conflicts_with = "root")]
+    name: Option<String>,
+    /// Name configs by their path relative to this directory instead of by
+    /// their file name, so `services/api/config.yaml` and
+    /// `services/worker/config.yaml` stay apart.
+    #[arg(long)]
+    root: Option<PathBuf>,
+    /// Format of the config read from stdin. Sniffed from the contents if not given.
+    #[arg(long)]
+    format: Option<String>,
+    /// Name the documents of a multi document file by the value at this dotted
+    /// key path, eg. `metadata.name`, instead of by their index. Documents are
+    /// stored as `<name>#<index>` or `<name>#<value>`, while a file with a single
+    /// document is stored as `<name>`. A file gaining a second document therefore
+    /// starts new families rather than continuing `<name>`.
+    #[arg(long)]
+    doc_key: Option<String>,
+}
+/// Narrows the files found in directories and glob patterns, on top of any
+/// `.deltaignore` files.
+#[derive(Debug, Clone, Args)]
+struct FilterArgs {
+    /// Only add files matching this gitignore style glob, can be repeated.
+    #[arg(long)]
+    include: Vec<String>,
+    /// Skip files matching this gitignore style glob, can be repeated.
+    #[arg(long)]
+    exclude: Vec<String>,
+}
+impl From<FilterArgs> for PathFilter {
+    fn from(args: FilterArgs) -> Self {
+        PathFilter {
+            include: args.include,
+            exclude: args.exclude,
+        }
+    }
+}
+impl SourceArgs {
+    fn check(&self, paths: &[PathBuf]) -> anyhow::Result<()> {
+        let stdin_count = paths.iter().filter(|p| *p == Path::new(STDIN_PATH)).count();
+        if stdin_count > 1 {
+            return Err(anyhow!("stdin can only be added once."));
+        }
+        if stdin_count == 1 && self.name.is_none() {
+            return Err(anyhow!("Adding a config from stdin requires --name."));
+        }
+        let naming = self.naming();
+        let named = paths
+            .iter()
+            .filter_map(|p| match p == Path::new(STDIN_PATH) {
+                true => Some((self.name.clone()?, p.as_path())),
+                false => Some((naming.name(p).ok()?, p.as_path())),
+            });
+        for (name, colliding) in name_collisions(named) {
+            let colliding = colliding
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            eprintln!(
+                "Warning: {} are all stored as config {}.",
+                colliding.join(", "),
+                name
+            );
+            if matches!(naming, Naming::Basename) {
+                eprintln!("Use --root to name configs by their relative path instead.");
+            }
+        }
+        Ok(())
+    }
+    fn naming(&self) -> Naming {
+        match (&self.name, &self.root) {
+            (Some(name), _) => Naming::Explicit(name.clone()),
+            (None, Some(root)) => Naming::Relative(root.clone()),
+            (None, None) => Naming::Basename,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Subcommand)]
 enum Modes {
     List,
@@ -223,9 +993,229 @@ enum Modes {
         interactive: bool,
     },
     Add {
+        /// Config files, directories or quoted glob patterns such as
+        /// 'example_data/**/*.yaml' to add, `-` reads a config from stdin.
+        paths: Vec<PathBuf>,
+        /// Add every config or none of them, if any file fails.
+        #[arg(long, default_value_t = false)]
+        atomic: bool,
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Add configs whenever they're saved, like running add after every edit.
+    Watch {
+        /// Config files, directories or quoted glob patterns to watch.
+        #[arg(required = true)]
         paths: Vec<PathBuf>,
+        /// Milliseconds to wait for writes to settle before adding a file.
+        #[arg(long, default_value_t = 500)]
+        debounce: u64,
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
     },
+    /// Print a stored delta by id, or the newest full config of a family.
     Get {
-        delta_id: i64,
+        /// A delta id, or a config name with an optional version, eg. run.yaml@2.
+        target: ConfigRef,
+        /// Output format, one of the registered config formats eg. json, yaml, toml or env.
+        #[arg(short, long, default_value = "json")]
+        format: String,
+        /// Indent the output for reading.
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+        /// Get the config that was current at this time, across versions. Times
+        /// without an offset are UTC, like the times `log` prints.
+        /// eg. "2026-09-01T12:00", "2026-09-01 12:00:00" or "2026-09-01T14:00+02:00".
+        #[arg(long, value_parser = parse_timestamp)]
+        at: Option<NaiveDateTime>,
     },
+    /// Write a stored config back to disk, by default to the file it was added from.
+    Checkout {
+        /// A delta id, or a config name with an optional version, eg. run.yaml@2.
+        target: ConfigRef,
+        /// Where to write the config, defaults to its name.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Format to write, defaults to the format of the output's extension.
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Overwrite the output even if it holds changes that aren't stored.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+    /// List the stored configs of a family oldest first, with a summary of the
+    /// keys each one added (+), removed (-) and changed (~).
+    Log {
+        /// Config name eg. run.yaml.
+        name: String,
+        /// Include every shape version instead of only the latest.
+        #[arg(long, default_value_t = false)]
+        all_versions: bool,
+    },
+    /// List the stored configs of a family whose values match a predicate.
+    Query {
+        /// Config name eg. run.yaml.
+        name: String,
+        /// Comparisons of key paths with values joined by and, or and not,
+        /// eg. "optimizer.lr > 0.01 and model.layers[0].size == 12".
+        /// Strings are quoted or bare words, true, false and null match json types.
+        predicate: String,
+    },
+    /// Remove stored configs, after listing them and asking for confirmation.
+    /// Version numbers of removed versions aren't reused.
+    Rm {
+        /// A delta id, a config name with a version to remove that version,
+        /// eg. run.yaml@2, or a bare name to remove every version of it.
+        target: ConfigRef,
+        /// Only list what would be removed.
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Don't ask for confirmation.
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+    },
+    /// Tag a stored config, eg. with a release name, or list its tags.
+    Tag {
+        /// Delta id of the config.
+        id: i64,
+        /// Tags to add, without any the config's tags are listed.
+        tags: Vec<String>,
+        /// Remove the tags instead.
+        #[arg(long, default_value_t = false)]
+        remove: bool,
+    },
+    /// Show or set which configs of a family gc keeps. A config is kept if any
+    /// rule keeps it and the newest is always kept. Setting a policy needs
+    /// --keep-last or --keep-days.
+    Retention {
+        /// Config name eg. run.yaml.
+        name: String,
+        /// Keep the newest N configs, across all versions.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        keep_last: Option<u32>,
+        /// Keep configs added within the last N days.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        keep_days: Option<u32>,
+        /// Keep every tagged config.
+        #[arg(long, default_value_t = false)]
+        keep_tagged: bool,
+        /// Remove the policy, gc then keeps every config of the family.
+        #[arg(long, default_value_t = false, conflicts_with_all = ["keep_last", "keep_days", "keep_tagged"])]
+        clear: bool,
+    },
+    /// Apply retention policies, collapse duplicate deltas and compact the database.
+    Gc,
+    /// Search the values and key paths of every stored config, across all names.
+    Grep {
+        /// Text to find in a config's `key.path = value` lines, ignoring case.
+        /// String values are matched in quotes, eg. '"prod"'.
+        pattern: String,
+    },
+    /// Show the delta that last set each value of a config.
+    Blame {
+        /// Config name eg. run.yaml.
+        name: String,
+        /// Which version of the base config to use. Defaults to the latest.
+        version: Option<i64>,
+    },
+    /// Find the first delta of a config that breaks a command, by binary search
+    /// between a good and a bad delta. The command gets the config's path in
+    /// $DELTA_CONFIG and its delta id in $DELTA_ID, a zero exit status is good.
+    Bisect {
+        /// Config name eg. run.yaml.
+        name: String,
+        /// A delta id known to be good.
+        #[arg(long)]
+        good: i64,
+        /// A newer delta id known to be bad.
+        #[arg(long)]
+        bad: i64,
+        /// The command to test each config with, after `--`.
+        #[arg(last = true, required = true)]
+        cmd: Vec<String>,
+    },
+    /// Show the key paths added, removed and changed between two configs.
+    Diff {
+        /// A config file, a delta id or a config name with an optional version.
+        /// Files take precedence over stored names.
+        old: String,
+        /// Compared against old, in the same forms.
+        new: String,
+        /// Print the changes as a json list instead of text.
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+/// Parse a time given on the command line into UTC.
+fn parse_timestamp(s: &str) -> Result<NaiveDateTime, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.naive_utc());
+    }
+    for format in ["%Y-%m-%dT%H:%M%#z", "%Y-%m-%dT%H:%M:%S%#z"] {
+        if let Ok(t) = DateTime::parse_from_str(s, format) {
+            return Ok(t.naive_utc());
+        }
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(t);
+        }
+    }
+    match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        Ok(d) => Ok(d.and_time(NaiveTime::MIN)),
+        Err(_) => Err(format!("Expected a time like 2026-09-01T12:00, got {}", s)),
+    }
+}
+/// A stored config, either a delta by id or a config family by name and version.
+#[derive(Debug, Clone)]
+enum ConfigRef {
+    Delta(i64),
+    Named { name: String, version: Option<i64> },
+}
+impl ConfigRef {
+    /// The config's name and its full contents, rebuilt from its base and delta.
+    fn resolve(self, s: &Store) -> anyhow::Result<(String, Value)> {
+        match self {
+            ConfigRef::Delta(id) => Ok((s.get_delta_origin(id)?.0, s.get_delta(id)?)),
+            ConfigRef::Named { name, version } => match s.get_latest_config(&name, version)? {
+                Some(cfg) => Ok((name, cfg)),
+                None => Err(DeltaError::ConfigNotFound {
+                    name,
+                    version,
+                    available: s.get_base_configs()?,
+                }
+                .into()),
+            },
+        }
+    }
+}
+impl FromStr for ConfigRef {
+    type Err = Infallible;
+
+    /// Numbers are delta ids, `<name>@<version>` picks a version and any other
+    /// string is a name's newest config across its versions.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse() {
+            return Ok(ConfigRef::Delta(id));
+        }
+        let named = match s.rsplit_once('@') {
+            Some((name, version)) => version.parse().ok().map(|v| (name, Some(v))),
+            None => None,
+        };
+        let (name, version) = named.unwrap_or((s, None));
+        Ok(ConfigRef::Named {
+            name: name.to_string(),
+            version,
+        })
+    }
 }
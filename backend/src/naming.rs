@@ -0,0 +1,135 @@
+use crate::{DeltaError, Result};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+/// How a config file's path maps to the name its versions are stored under.
+#[derive(Debug, Clone, Default)]
+pub enum Naming {
+    /// The file name, `services/api/config.yaml` is `config.yaml`.
+    #[default]
+    Basename,
+    /// The path relative to a project root, `services/api/config.yaml`.
+    /// Separators are always `/` so names match across platforms.
+    Relative(PathBuf),
+    /// The same name for every path.
+    Explicit(String),
+}
+impl Naming {
+    pub fn name(&self, path: &Path) -> Result<String> {
+        let invalid = || DeltaError::InvalidPath(path.to_path_buf());
+        match self {
+            Naming::Basename => path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(str::to_string)
+                .ok_or_else(invalid),
+            Naming::Relative(root) => {
+                let root = root.canonicalize()?;
+                let path = path.canonicalize()?;
+                let relative = path.strip_prefix(&root).map_err(|_| invalid())?;
+                let parts = relative
+                    .components()
+                    .map(|c| match c {
+                        Component::Normal(p) => p.to_str(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|p| !p.is_empty())
+                    .ok_or_else(invalid)?;
+                Ok(parts.join("/"))
+            }
+            Naming::Explicit(name) => Ok(name.clone()),
+        }
+    }
+}
+
+/// Names that more than one distinct path maps to, along with those paths.
+/// Storing such paths under one name mixes unrelated configs into one family.
+///
+/// Paths are compared once resolved, so `a.yaml`, `./a.yaml` and a symlink to it
+/// are the same file. Paths that can't be resolved are compared without `.` parts.
+pub fn name_collisions<'a>(
+    named: impl IntoIterator<Item = (String, &'a Path)>,
+) -> Vec<(String, Vec<&'a Path>)> {
+    let mut by_name = BTreeMap::<String, Vec<(PathBuf, &Path)>>::new();
+    for (name, path) in named {
+        let resolved = path.canonicalize().unwrap_or_else(|_| {
+            path.components()
+                .filter(|c| !matches!(c, Component::CurDir))
+                .collect()
+        });
+        let paths = by_name.entry(name).or_default();
+        if !paths.iter().any(|(r, _)| *r == resolved) {
+            paths.push((resolved, path));
+        }
+    }
+    by_name
+        .into_iter()
+        .filter(|(_, p)| p.len() > 1)
+        .map(|(name, p)| (name, p.into_iter().map(|(_, path)| path).collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test_naming {
+    use super::*;
+
+    #[test]
+    fn test_naming() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let path = root.join("src/lib.rs");
+        assert_eq!(Naming::Basename.name(&path).unwrap(), "lib.rs");
+        assert_eq!(
+            Naming::Relative(root.clone()).name(&path).unwrap(),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            Naming::Relative(root.join("src/../src"))
+                .name(&root.join("./src/lib.rs"))
+                .unwrap(),
+            "lib.rs"
+        );
+        assert!(matches!(
+            Naming::Relative(root.join("src")).name(&root.join("Cargo.toml")),
+            Err(DeltaError::InvalidPath(_))
+        ));
+        assert_eq!(
+            Naming::Explicit(String::from("api")).name(&path).unwrap(),
+            "api"
+        );
+    }
+    #[test]
+    fn test_name_collisions() {
+        let api = Path::new("services/api/config.yaml");
+        let worker = Path::new("services/worker/config.yaml");
+        let named = [api, worker, api, Path::new("run.yaml")]
+            .into_iter()
+            .map(|p| (Naming::Basename.name(p).unwrap(), p));
+        assert_eq!(
+            name_collisions(named),
+            vec![(String::from("config.yaml"), vec![api, worker])]
+        );
+        let relative = [Path::new("a.yaml"), Path::new("./a.yaml")]
+            .into_iter()
+            .map(|p| (Naming::Basename.name(p).unwrap(), p));
+        assert!(name_collisions(relative).is_empty());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_name_collisions_resolve_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.yaml");
+        std::fs::write(&file, "a: 1").unwrap();
+        std::fs::create_dir(dir.path().join("link")).unwrap();
+        let link = dir.path().join("link/config.yaml");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let dotted = dir.path().join("link/../config.yaml");
+        let named = [&file, &link, &dotted]
+            .into_iter()
+            .map(|p| (Naming::Basename.name(p).unwrap(), p.as_path()));
+        assert!(name_collisions(named).is_empty());
+    }
+}
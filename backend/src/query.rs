@@ -0,0 +1,361 @@
+//! Value predicates over stored configs, eg. `optimizer.lr > 0.01 and model.layers == 12`,
+//! compiled to sqlite json functions over `BaseCfgs.cfg` and `Deltas.delta`.
+use crate::{DeltaError, Result, ARRAY_LEN_KEY, ARRAY_PATCH_KEY, ARRAY_SET_KEY};
+
+/// A value bound to a compiled predicate's placeholders, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Bind {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Compile a predicate into a sql boolean expression and its bound values.
+///
+/// Grammar, keywords are case insensitive:
+/// ```text
+/// expr       := and_expr ("or" and_expr)*
+/// and_expr   := unary ("and" unary)*
+/// unary      := "not" unary | "(" expr ")" | path op literal
+/// op         := "==" | "!=" | ">" | ">=" | "<" | "<="
+/// literal    := number | "string" | 'string' | true | false | null | bare word
+/// ```
+/// Paths are keys joined with `.` and array indices as `[0]`, eg. `servers[0].port`.
+pub(crate) fn compile(predicate: &str) -> Result<(String, Vec<Bind>)> {
+    let tokens = tokenize(predicate)?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        binds: vec![],
+    };
+    let sql = parser.expr()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(invalid(format!("unexpected {}", describe(Some(token)))));
+    }
+    Ok((sql, parser.binds))
+}
+
+fn invalid(msg: impl Into<String>) -> DeltaError {
+    DeltaError::InvalidQuery(msg.into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(&'static str),
+    Word(String),
+    Quoted(String),
+}
+/// Describe a token, or the end of the predicate, for error messages.
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => String::from("end of query"),
+        Some(Token::Open) => String::from("'('"),
+        Some(Token::Close) => String::from("')'"),
+        Some(Token::Op(op)) => format!("'{}'", op),
+        Some(Token::Word(w)) => format!("'{}'", w),
+        Some(Token::Quoted(s)) => format!("{:?}", s),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    const OPS: [&str; 6] = ["==", "!=", ">=", "<=", ">", "<"];
+    let mut tokens = vec![];
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            rest = &rest[1..];
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| invalid(format!("unclosed quote in {}", rest)))?;
+            tokens.push(Token::Quoted(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "()=!<>\"'".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid(format!("unexpected {}", rest)));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    binds: Vec<Bind>,
+}
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn expr(&mut self) -> Result<String> {
+        let mut sql = self.and_expr()?;
+        while self.keyword("or") {
+            sql = format!("{} OR {}", sql, self.and_expr()?);
+        }
+        Ok(sql)
+    }
+    fn and_expr(&mut self) -> Result<String> {
+        let mut sql = self.unary()?;
+        while self.keyword("and") {
+            sql = format!("{} AND {}", sql, self.unary()?);
+        }
+        Ok(sql)
+    }
+    fn unary(&mut self) -> Result<String> {
+        if self.keyword("not") {
+            return Ok(format!("NOT {}", self.unary()?));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let sql = self.expr()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(format!("({})", sql)),
+                    other => Err(invalid(format!(
+                        "expected ')', got {}",
+                        describe(other.as_ref())
+                    ))),
+                }
+            }
+            Some(Token::Word(path)) => self.comparison(&path),
+            other => Err(invalid(format!(
+                "expected a key path, got {}",
+                describe(other.as_ref())
+            ))),
+        }
+    }
+    fn comparison(&mut self, path: &str) -> Result<String> {
+        let segments = parse_path(path)?;
+        let Some(Token::Op(op)) = self.next() else {
+            return Err(invalid(format!("expected a comparison after {}", path)));
+        };
+        let sql_op = if op == "==" { "=" } else { op };
+        let literal = match self.next() {
+            Some(Token::Quoted(s)) => {
+                self.binds.push(Bind::Text(s));
+                None
+            }
+            Some(Token::Word(w)) => match w.as_str() {
+                "true" | "false" | "null" => Some(w),
+                _ => {
+                    let bind = match (w.parse::<i64>(), w.parse::<f64>()) {
+                        (Ok(i), _) => Bind::Int(i),
+                        (_, Ok(f)) => Bind::Float(f),
+                        _ => Bind::Text(w),
+                    };
+                    self.binds.push(bind);
+                    None
+                }
+            },
+            other => {
+                let got = describe(other.as_ref());
+                return Err(invalid(format!(
+                    "expected a value after {}, got {}",
+                    path, got
+                )));
+            }
+        };
+        match literal {
+            None => Ok(format!(
+                "{} {} ?",
+                effective("json_extract", "$", "$", &segments),
+                sql_op
+            )),
+            // json_extract turns true, false and null into 1, 0 and NULL, compare types instead.
+            Some(json_type) => {
+                let sql_op = match op {
+                    "==" => "IS",
+                    "!=" => "IS NOT",
+                    _ => {
+                        return Err(invalid(format!(
+                            "{} can only be compared with == or !=",
+                            json_type
+                        )))
+                    }
+                };
+                Ok(format!(
+                    "{} {} '{}'",
+                    effective("json_type", "$", "$", &segments),
+                    sql_op,
+                    json_type
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>> {
+    let bad_path = || invalid(format!("invalid key path {}", path));
+    let mut segments = vec![];
+    for part in path.split('.') {
+        let (key, mut indices) = part.split_once('[').map_or((part, ""), |(k, i)| (k, i));
+        let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '$';
+        if key.is_empty() || !key.chars().all(valid) {
+            return Err(bad_path());
+        }
+        segments.push(Segment::Key(key.to_string()));
+        while !indices.is_empty() {
+            let (idx, rest) = indices.split_once(']').ok_or_else(bad_path)?;
+            segments.push(Segment::Index(idx.parse().map_err(|_| bad_path())?));
+            indices = rest.strip_prefix('[').unwrap_or(rest);
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(bad_path());
+            }
+        }
+    }
+    Ok(segments)
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    format!("{}.\"{}\"", path, key)
+}
+fn index_path(path: &str, idx: usize) -> String {
+    format!("{}[{}]", path, idx)
+}
+fn full_path(path: &str, segments: &[Segment]) -> String {
+    segments.iter().fold(path.to_string(), |p, s| match s {
+        Segment::Key(k) => key_path(&p, k),
+        Segment::Index(i) => index_path(&p, *i),
+    })
+}
+
+/// Sql for `func` applied to the effective value at `segments`, below the path
+/// `delta` into `Deltas.delta` and the path `base` into `BaseCfgs.cfg`.
+///
+/// Keys the delta doesn't hold fall back to the base. Arrays are either replaced
+/// whole in the delta, or patched element wise as in `build_cfg_from_base_and_delta`.
+fn effective(func: &str, delta: &str, base: &str, segments: &[Segment]) -> String {
+    let in_delta = |path: &str| format!("{}(Deltas.delta, '{}')", func, path);
+    let in_base = |path: &str| format!("{}(BaseCfgs.cfg, '{}')", func, path);
+    let delta_type = |path: &str| format!("json_type(Deltas.delta, '{}')", path);
+    match segments.split_first() {
+        None => format!(
+            "(CASE WHEN {} IS NOT NULL THEN {} ELSE {} END)",
+            delta_type(delta),
+            in_delta(delta),
+            in_base(base)
+        ),
+        Some((Segment::Key(k), rest)) => {
+            effective(func, &key_path(delta, k), &key_path(base, k), rest)
+        }
+        Some((Segment::Index(i), rest)) => {
+            let set = index_key(&key_path(delta, ARRAY_SET_KEY), *i);
+            let patch = index_key(&key_path(delta, ARRAY_PATCH_KEY), *i);
+            let len = key_path(delta, ARRAY_LEN_KEY);
+            let base_elem = index_path(base, *i);
+            format!(
+                "(CASE WHEN {dt} IS NULL THEN {base} \
+                WHEN {dt} = 'array' THEN {whole} \
+                WHEN {set_t} IS NOT NULL THEN {set} \
+                WHEN {patch_t} IS NOT NULL THEN {patch} \
+                WHEN json_extract(Deltas.delta, '{len}') <= {i} THEN NULL \
+                ELSE {base} END)",
+                dt = delta_type(delta),
+                base = in_base(&full_path(&base_elem, rest)),
+                whole = in_delta(&full_path(&index_path(delta, *i), rest)),
+                set_t = delta_type(&set),
+                set = in_delta(&full_path(&set, rest)),
+                patch_t = delta_type(&patch),
+                patch = effective(func, &patch, &base_elem, rest),
+                len = len,
+                i = i,
+            )
+        }
+    }
+}
+/// Path to an index keyed entry of `$set` or `$patch`.
+fn index_key(path: &str, idx: usize) -> String {
+    key_path(path, &idx.to_string())
+}
+
+#[cfg(test)]
+mod test_query {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("a.b[0][2].c").unwrap(),
+            vec![
+                Segment::Key(String::from("a")),
+                Segment::Key(String::from("b")),
+                Segment::Index(0),
+                Segment::Index(2),
+                Segment::Key(String::from("c")),
+            ]
+        );
+        for bad in ["a..b", "a[x]", "a[0", "a[0]b", "[0]", "a'b"] {
+            assert!(parse_path(bad).is_err(), "{}", bad);
+        }
+    }
+    #[test]
+    fn test_compile() {
+        let (sql, binds) = compile("a.b > 0.5 and (c == 'x y' or not d != 3)").unwrap();
+        assert_eq!(sql.matches(" AND ").count(), 1);
+        assert!(sql.contains(" OR NOT "));
+        assert_eq!(
+            binds,
+            vec![
+                Bind::Float(0.5),
+                Bind::Text(String::from("x y")),
+                Bind::Int(3)
+            ]
+        );
+        let (sql, binds) = compile("flag == true AND name != null").unwrap();
+        assert!(sql.contains("IS 'true'") && sql.contains("IS NOT 'null'"));
+        assert!(binds.is_empty());
+        assert_eq!(
+            compile("env == prod").unwrap().1,
+            vec![Bind::Text(String::from("prod"))]
+        );
+    }
+    #[test]
+    fn test_compile_errors() {
+        for bad in [
+            "",
+            "a ==",
+            "a 1",
+            "a == 1 and",
+            "(a == 1",
+            "a == 1)",
+            "a > true",
+            "a == 'x",
+            "== 1",
+        ] {
+            assert!(
+                matches!(compile(bad), Err(DeltaError::InvalidQuery(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{DeltaError, HistoryEntry};
+use chrono::{Duration, NaiveDateTime};
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+/// Which stored configs of a family `gc` keeps, a config is kept if any rule
+/// keeps it. The newest config of a family is always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep the newest this many configs, across all versions.
+    pub keep_last: Option<u32>,
+    /// Keep configs added within this many days.
+    pub keep_days: Option<u32>,
+    /// Keep configs with at least one tag.
+    pub keep_tagged: bool,
+}
+impl RetentionPolicy {
+    /// Reject rules that keep nothing, the newest config is kept regardless.
+    /// A policy needs a count, `keep_tagged` alone would expire every untagged
+    /// config but the newest.
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.keep_last.is_none() && self.keep_days.is_none() {
+            return Err(DeltaError::InvalidPolicy(String::from(
+                "set keep_last or keep_days",
+            )));
+        }
+        if self.keep_last == Some(0) {
+            return Err(DeltaError::InvalidPolicy(String::from(
+                "keep_last must be at least 1",
+            )));
+        }
+        if self.keep_days == Some(0) {
+            return Err(DeltaError::InvalidPolicy(String::from(
+                "keep_days must be at least 1",
+            )));
+        }
+        Ok(())
+    }
+    /// The delta ids of a family's configs the policy doesn't keep at `now`.
+    /// `history` is ordered oldest first, as returned by `get_history`.
+    pub(crate) fn expired(
+        &self,
+        history: &[HistoryEntry],
+        tagged: &HashSet<i64>,
+        now: NaiveDateTime,
+    ) -> Vec<i64> {
+        let keep_last = self.keep_last.unwrap_or(0).max(1) as usize;
+        // Spans reaching past the earliest representable time keep every config.
+        let cutoff = self.keep_days.map(|d| {
+            Duration::try_days(d.into())
+                .and_then(|d| now.checked_sub_signed(d))
+                .unwrap_or(NaiveDateTime::MIN)
+        });
+        history[..history.len().saturating_sub(keep_last)]
+            .iter()
+            .filter(|e| cutoff.is_none_or(|c| e.created_at < c))
+            .filter(|e| !(self.keep_tagged && tagged.contains(&e.id)))
+            .map(|e| e.id)
+            .collect()
+    }
+}
+impl Display for RetentionPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut rules = vec![];
+        if let Some(n) = self.keep_last {
+            rules.push(format!("the last {}", n));
+        }
+        if let Some(d) = self.keep_days {
+            rules.push(format!("newer than {} days", d));
+        }
+        if self.keep_tagged {
+            rules.push(String::from("tagged"));
+        }
+        match rules.is_empty() {
+            true => write!(f, "keep only the newest"),
+            false => write!(f, "keep {}", rules.join(", ")),
+        }
+    }
+}
+
+/// What `gc` removed, and the database size around it in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// Configs removed by retention policies.
+    pub expired: u64,
+    /// Delta rows removed for repeating the config stored right before them.
+    pub duplicates: u64,
+    pub size_before: i64,
+    pub size_after: i64,
+}
+impl GcReport {
+    pub fn reclaimed(&self) -> i64 {
+        self.size_before - self.size_after
+    }
+}
+
+#[cfg(test)]
+mod test_retention {
+    use super::*;
+    use serde_json::json;
+
+    fn history(days_ago: &[i64], now: NaiveDateTime) -> Vec<HistoryEntry> {
+        days_ago
+            .iter()
+            .enumerate()
+            .map(|(i, d)| HistoryEntry {
+                id: i as i64 + 1,
+                version: 0,
+                is_base: i == 0,
+                created_at: now - Duration::days(*d),
+                cfg: json!({"a": i}),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_expired() {
+        let now =
+            NaiveDateTime::parse_from_str("2026-10-17 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let history = history(&[400, 300, 200, 100, 50, 10, 1], now);
+        let tagged = HashSet::from([2]);
+        let policy = |keep_last, keep_days, keep_tagged| RetentionPolicy {
+            keep_last,
+            keep_days,
+            keep_tagged,
+        };
+        assert_eq!(
+            policy(Some(3), None, false).expired(&history, &tagged, now),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            policy(None, Some(90), false).expired(&history, &tagged, now),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            policy(Some(2), Some(90), true).expired(&history, &tagged, now),
+            vec![1, 3, 4]
+        );
+        assert_eq!(
+            policy(None, None, false).expired(&history, &tagged, now),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert!(policy(Some(10), None, false)
+            .expired(&history, &tagged, now)
+            .is_empty());
+        assert!(policy(None, Some(u32::MAX), false)
+            .expired(&history, &tagged, now)
+            .is_empty());
+        assert!(policy(Some(1), Some(1), true).validate().is_ok());
+        assert!(matches!(
+            policy(Some(0), None, false).validate(),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            policy(None, Some(0), false).validate(),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            policy(None, None, true).validate(),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            RetentionPolicy::default().validate(),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        assert_eq!(
+            policy(Some(2), Some(90), true).to_string(),
+            "keep the last 2, newer than 90 days, tagged"
+        );
+    }
+}
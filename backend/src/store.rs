@@ -0,0 +1,2123 @@
+use crate::{
+    build_cfg_from_base_and_delta, calculate_cfg_hash, calculate_delta,
+    diff::leaf_paths,
+    query::{compile, Bind},
+    DeltaError, GcReport, Result, RetentionPolicy, CFG_HASH_VERSION,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde_json::{Map, Value};
+use sqlx::{
+    sqlite::SqliteConnectOptions, types::Json, Row, Sqlite, SqliteConnection, SqlitePool,
+    Transaction,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use tokio::runtime::Runtime;
+use tracing::{debug, info, warn};
+
+/// What adding a config to the store did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// The config's shape was new, it was stored as this base version.
+    NewBase { version: i64 },
+    /// The config was stored as a delta against an existing base.
+    NewDelta { id: i64 },
+    /// The config is identical to the newest one of its name, stored as `existing_id`.
+    Unchanged { existing_id: i64 },
+}
+
+/// One stored config of a family, see `AsyncStore::get_history`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// The delta id.
+    pub id: i64,
+    /// The shape version the delta belongs to.
+    pub version: i64,
+    /// Whether this is the row created along with its shape version.
+    pub is_base: bool,
+    /// When the config was added, in UTC.
+    pub created_at: NaiveDateTime,
+    /// The full config, rebuilt from its base and delta.
+    pub cfg: Value,
+}
+
+/// The delta that last set a leaf of a config, see `AsyncStore::blame`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameEntry {
+    /// Key path of the leaf, eg. `server.hosts[0]`.
+    pub path: String,
+    pub value: Value,
+    /// The delta id that set the value.
+    pub id: i64,
+    pub created_at: NaiveDateTime,
+}
+
+/// A leaf of a stored config whose text matched, see `AsyncStore::search_text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextHit {
+    pub name: String,
+    pub version: i64,
+    /// The delta id of the config.
+    pub id: i64,
+    /// Key path of the leaf, eg. `server.hosts[0]`.
+    pub path: String,
+    pub value: Value,
+}
+
+/// Config store whose operations are futures run on the caller's runtime.
+#[derive(Clone)]
+pub struct AsyncStore {
+    pool: SqlitePool,
+}
+impl AsyncStore {
+    pub async fn new(url: impl AsRef<str>) -> Result<AsyncStore> {
+        // Zero the pages of removed configs, `rm` is how secrets are purged.
+        let options = SqliteConnectOptions::from_str(url.as_ref())?.pragma("secure_delete", "ON");
+        let pool = SqlitePool::connect_with(options).await?;
+        sqlx::migrate!().run(&pool).await?;
+        let store = AsyncStore { pool };
+        store.migrate_cfg_hashes().await?;
+        store.index_missing_text().await?;
+        Ok(store)
+    }
+    /// Begin a transaction that takes the write lock up front, as `BEGIN IMMEDIATE`.
+    /// Deferred transactions that read before writing can't upgrade their lock while
+    /// another writer is active, sqlite then fails them with SQLITE_BUSY instead of
+    /// waiting for the busy timeout.
+    async fn begin_write(&self) -> Result<Transaction<'static, Sqlite>> {
+        let mut tx = self.pool.begin().await?;
+        // sqlx can't choose the transaction mode, so replace its deferred transaction.
+        sqlx::query("ROLLBACK").execute(&mut *tx).await?;
+        sqlx::query("BEGIN IMMEDIATE").execute(&mut *tx).await?;
+        Ok(tx)
+    }
+    /// Rewrite every cfg_hash that wasn't produced by the current `calculate_cfg_hash`.
+    /// This keeps databases written by older builds, which used `DefaultHasher`, readable.
+    async fn migrate_cfg_hashes(&self) -> Result<()> {
+        let current = format!("{}:%", CFG_HASH_VERSION);
+        let stale = sqlx::query!(
+            r#"SELECT id, cfg_hash, cfg as "cfg: Value" FROM BaseCfgs WHERE cfg_hash NOT LIKE $1"#,
+            current
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if stale.is_empty() {
+            return Ok(());
+        }
+        info!("Rewriting {} outdated config hashes.", stale.len());
+        let mut tx = self.begin_write().await?;
+        for row in stale {
+            let hash = calculate_cfg_hash(&row.cfg)?;
+            debug!("Rewriting cfg_hash {} to {}", row.cfg_hash, hash);
+            sqlx::query!(
+                "UPDATE BaseCfgs SET cfg_hash = $1 WHERE id = $2",
+                hash,
+                row.id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+    /// Index the text of every config that isn't in `ConfigText` yet, such as
+    /// configs stored by builds that didn't index their text.
+    async fn index_missing_text(&self) -> Result<()> {
+        let missing = sqlx::query!(
+            r#"SELECT Deltas.id, Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value"
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE NOT Deltas.text_indexed"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        info!("Indexing the text of {} configs.", missing.len());
+        let mut tx = self.begin_write().await?;
+        for row in missing {
+            let cfg = build_cfg_from_base_and_delta(row.cfg, row.delta);
+            Self::index_text(&mut tx, row.id, &cfg).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+    /// Add a `key.path = value` line to `ConfigText` for every leaf of a delta's config.
+    async fn index_text(conn: &mut SqliteConnection, delta_id: i64, cfg: &Value) -> Result<()> {
+        for (path, value) in leaf_paths(cfg) {
+            let line = format!("{} = {}", path, value);
+            sqlx::query!(
+                "INSERT INTO ConfigText (line, path, delta_id) VALUES ($1, $2, $3)",
+                line,
+                path,
+                delta_id
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        sqlx::query!(
+            "UPDATE Deltas SET text_indexed = TRUE WHERE id = $1",
+            delta_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+    /// Insert a base config under the next version of its name, as `(id, version)`.
+    /// Versions follow the highest one ever given to the name, so numbers of removed
+    /// versions aren't reused.
+    async fn insert_base(
+        conn: &mut SqliteConnection,
+        cfg_name: &str,
+        cfg: &Value,
+        cfg_hash: &str,
+    ) -> Result<(i64, i64)> {
+        let version = sqlx::query_scalar!(
+            r#"
+            INSERT INTO VersionCounters (name, last_version)
+            VALUES ($1, (SELECT COALESCE(MAX(version) + 1, 0) FROM BaseCfgs WHERE name = $1))
+            ON CONFLICT (name) DO UPDATE
+            SET last_version = MAX(last_version + 1, excluded.last_version)
+            RETURNING last_version;"#,
+            cfg_name
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO BaseCfgs (name, cfg, version, cfg_hash) 
+            VALUES ($1, $2, $3, $4) RETURNING id;"#,
+            cfg_name,
+            cfg,
+            version,
+            cfg_hash
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok((id, version))
+    }
+    async fn add_base_config(
+        conn: &mut SqliteConnection,
+        cfg_name: &str,
+        cfg: serde_json::Value,
+    ) -> Result<i64> {
+        let hash_str = calculate_cfg_hash(&cfg)?;
+        let (id, version) = Self::insert_base(conn, cfg_name, &cfg, &hash_str).await?;
+        info!("Succesfully added base config");
+        let marker = Value::Object(Map::new());
+        let marker_id = sqlx::query_scalar!(
+            r#"INSERT INTO Deltas (base_id, delta, is_base) VALUES ($1, $2, TRUE) RETURNING id"#,
+            id,
+            marker
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Self::index_text(conn, marker_id, &cfg).await?;
+        Ok(version)
+    }
+
+    pub async fn get_base_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Option<Value>> {
+        let ref_ = cfg_name.as_ref();
+        let cfg = match version {
+            Some(v) => {
+                sqlx::query_scalar!(
+                    r#"SELECT cfg as "cfg: Value" 
+                    FROM BaseCfgs
+                    WHERE name = $1 and version = $2;"#,
+                    ref_,
+                    v,
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!(
+                    r#"SELECT cfg as "cfg: Value"
+                    FROM BaseCfgs 
+                    WHERE name = $1 and version = (
+                    SELECT MAX(version) FROM BaseCfgs where name = $1
+                    );"#,
+                    ref_,
+                )
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        };
+        Ok(cfg)
+    }
+    /// Find the base of a config name with a given shape, as `(id, cfg)`.
+    async fn get_base_config_by_hash(
+        conn: &mut SqliteConnection,
+        cfg_name: &str,
+        cfg_hash: &str,
+    ) -> Result<Option<(i64, Value)>> {
+        let row = sqlx::query!(
+            r#"SELECT id, cfg as "cfg: Value" FROM BaseCfgs WHERE name = $1 AND cfg_hash = $2;"#,
+            cfg_name,
+            cfg_hash
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(row.map(|r| (r.id, r.cfg)))
+    }
+    /// The newest stored config of a name across all its versions, as `(delta id, cfg)`.
+    async fn current_config(
+        conn: &mut SqliteConnection,
+        cfg_name: &str,
+    ) -> Result<Option<(i64, Value)>> {
+        let row = sqlx::query_file!("sql/query_latest_cfg.sql", cfg_name)
+            .fetch_optional(&mut *conn)
+            .await?;
+        Ok(row.map(|r| (r.id, build_cfg_from_base_and_delta(r.cfg, r.delta))))
+    }
+    /// Store a config as a delta against its base, or as a new base version
+    /// if no base of its name has the same shape.
+    async fn insert_config(
+        conn: &mut SqliteConnection,
+        cfg_name: &str,
+        cfg: serde_json::Value,
+    ) -> Result<AddOutcome> {
+        let hash_str = calculate_cfg_hash(&cfg)?;
+        // Only the current config counts, reverting to an older one, even of another
+        // version, is a change.
+        if let Some((existing_id, current)) = Self::current_config(conn, cfg_name).await? {
+            if current == cfg {
+                return Ok(AddOutcome::Unchanged { existing_id });
+            }
+        }
+        match Self::get_base_config_by_hash(conn, cfg_name, &hash_str).await? {
+            Some((base_id, base_cfg)) => {
+                debug!("Base Config found for {}", cfg_name);
+                // A config that reverted to its base still needs a row to become current again.
+                let delta =
+                    calculate_delta(&base_cfg, &cfg)?.unwrap_or_else(|| Value::Object(Map::new()));
+                debug!("Delta found {}", &delta);
+                let id = sqlx::query_scalar!(
+                    "INSERT INTO Deltas (base_id, delta) VALUES ($1, $2) RETURNING id",
+                    base_id,
+                    delta
+                )
+                .fetch_one(&mut *conn)
+                .await?;
+                Self::index_text(conn, id, &cfg).await?;
+                Ok(AddOutcome::NewDelta { id })
+            }
+            None => {
+                debug!("No Base Config found for {}", cfg_name);
+                let version = Self::add_base_config(conn, cfg_name, cfg).await?;
+                Ok(AddOutcome::NewBase { version })
+            }
+        }
+    }
+    /// Add a config, nothing is written if any part of the insert fails.
+    pub async fn add_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        cfg: serde_json::Value,
+    ) -> Result<AddOutcome> {
+        let mut tx = self.begin_write().await?;
+        let outcome = Self::insert_config(&mut tx, cfg_name.as_ref(), cfg).await?;
+        tx.commit().await?;
+        Ok(outcome)
+    }
+    /// Add many configs in a single transaction, either all of them are stored or none are.
+    /// The outcomes are in the same order as the configs.
+    pub async fn add_configs<S: AsRef<str>>(
+        &self,
+        cfgs: impl IntoIterator<Item = (S, serde_json::Value)>,
+    ) -> Result<Vec<AddOutcome>> {
+        let mut tx = self.begin_write().await?;
+        let mut outcomes = vec![];
+        for (cfg_name, cfg) in cfgs {
+            let outcome = Self::insert_config(&mut tx, cfg_name.as_ref(), cfg).await?;
+            outcomes.push(outcome);
+        }
+        tx.commit().await?;
+        Ok(outcomes)
+    }
+    /// Fetch the most recent config commited at a name and a version. If version is none,
+    /// the newest config across all versions, the one `get_config_at` gives for now.
+    pub async fn get_latest_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Option<Value>> {
+        let name = cfg_name.as_ref();
+        let (base_cfg, delta) = match version {
+            Some(v) => {
+                let Some(row) = sqlx::query_file!("sql/query_specific_version_cfg.sql", name, v)
+                    .fetch_optional(&self.pool)
+                    .await?
+                else {
+                    return Ok(None);
+                };
+                (row.cfg, row.delta)
+            }
+            None => {
+                let Some(row) = sqlx::query_file!("sql/query_latest_cfg.sql", name)
+                    .fetch_optional(&self.pool)
+                    .await?
+                else {
+                    return Ok(None);
+                };
+                (row.cfg, row.delta)
+            }
+        };
+        Ok(Some(build_cfg_from_base_and_delta(base_cfg, delta)))
+    }
+
+    /// Get the base configuration types and the number of versions they have
+    pub async fn get_base_configs(&self) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query!(r#"SELECT name, version FROM BaseCfgs"#)
+            .fetch_all(&self.pool)
+            .await;
+        match rows {
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => Ok(vec![]),
+                _ => Err(e.into()),
+            },
+            Ok(vec) => Ok(vec.into_iter().map(|r| (r.name, r.version)).collect()),
+        }
+    }
+    /// Id of the base config at a name and version, if version is none, then the latest version.
+    pub async fn get_base_config_id(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<i64> {
+        debug!(
+            "Querying base_cfg: {} ver {}",
+            cfg_name.as_ref(),
+            version
+                .map(|i| format!("{}", i))
+                .unwrap_or("latest".to_string()),
+        );
+        let cfg_name = cfg_name.as_ref();
+        let base_id = match version {
+            Some(v) => sqlx::query_scalar!(
+                        r#"SELECT id FROM BaseCfgs WHERE name=$1 and version=$2;"#,
+                        cfg_name,
+                        v
+                    )
+                    .fetch_optional(&self.pool).await?,
+            None => sqlx::query_scalar!(
+                r#"SELECT id FROM BaseCfgs WHERE name=$1 and version=(SELECT MAX(version) from BaseCfgs WHERE name=$1);"#, cfg_name
+            ).fetch_optional(&self.pool).await?,
+        };
+        match base_id {
+            Some(id) => Ok(id),
+            None => Err(DeltaError::ConfigNotFound {
+                name: cfg_name.to_string(),
+                version,
+                available: self.get_base_configs().await?,
+            }),
+        }
+    }
+    pub async fn get_all_deltas(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<u64>,
+    ) -> Result<Vec<(i64, Value)>> {
+        let cfg_name = cfg_name.as_ref();
+        let base_id = self
+            .get_base_config_id(cfg_name, version.map(|i| i as i64))
+            .await?;
+        let query_result = sqlx::query!(
+            r#"SELECT id, delta as "delta: Value" FROM Deltas WHERE base_id = $1 ORDER BY id"#,
+            base_id,
+        )
+        .fetch_all(&self.pool)
+        .await;
+        match query_result {
+            Err(e) => match e {
+                sqlx::Error::RowNotFound => {
+                    warn!(
+                        "No row was found cfg_name {}:{:?}, returning empty vec.",
+                        cfg_name, version,
+                    );
+                    Ok(vec![])
+                }
+                _ => Err(e.into()),
+            },
+            Ok(vec) => Ok(vec.into_iter().map(|row| (row.id, row.delta)).collect()),
+        }
+    }
+    pub async fn get_delta(&self, delta_id: i64) -> Result<Value> {
+        let row = sqlx::query!(
+            r#"SELECT Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value" FROM Deltas 
+                    INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id 
+                    WHERE Deltas.id = $1"#,
+            delta_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(DeltaError::DeltaNotFound(delta_id))?;
+        let (delta, base) = (row.delta, row.cfg);
+        Ok(build_cfg_from_base_and_delta(base, delta))
+    }
+    /// The config of a name that was current at a moment in UTC, across all its
+    /// versions. None if nothing had been stored under the name by then.
+    pub async fn get_config_at(
+        &self,
+        cfg_name: impl AsRef<str>,
+        at: NaiveDateTime,
+    ) -> Result<Option<Value>> {
+        let cfg_name = cfg_name.as_ref();
+        let row = sqlx::query!(
+            r#"SELECT Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value"
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE BaseCfgs.name = $1 AND datetime(Deltas.created_at) <= datetime($2)
+                    ORDER BY Deltas.created_at DESC, Deltas.id DESC LIMIT 1"#,
+            cfg_name,
+            at,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| build_cfg_from_base_and_delta(r.cfg, r.delta)))
+    }
+    /// Every stored config of a name across all its versions, oldest first.
+    pub async fn get_history(&self, cfg_name: impl AsRef<str>) -> Result<Vec<HistoryEntry>> {
+        let cfg_name = cfg_name.as_ref();
+        let history = Self::history(&mut *self.pool.acquire().await?, cfg_name).await?;
+        if history.is_empty() {
+            return Err(DeltaError::ConfigNotFound {
+                name: cfg_name.to_string(),
+                version: None,
+                available: self.get_base_configs().await?,
+            });
+        }
+        Ok(history)
+    }
+    async fn history(conn: &mut SqliteConnection, cfg_name: &str) -> Result<Vec<HistoryEntry>> {
+        let rows = sqlx::query!(
+            r#"SELECT Deltas.id, BaseCfgs.version, Deltas.is_base as "is_base: bool",
+                    Deltas.created_at as "created_at: NaiveDateTime",
+                    Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value"
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE BaseCfgs.name = $1
+                    ORDER BY Deltas.created_at, Deltas.id"#,
+            cfg_name,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| HistoryEntry {
+                id: row.id,
+                version: row.version,
+                is_base: row.is_base,
+                created_at: row.created_at,
+                cfg: build_cfg_from_base_and_delta(row.cfg, row.delta),
+            })
+            .collect())
+    }
+    /// For every leaf of the latest config at a name and version, the delta
+    /// that last set its value. If version is none, then the latest version.
+    pub async fn blame(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Vec<BlameEntry>> {
+        let cfg_name = cfg_name.as_ref();
+        let base_id = self.get_base_config_id(cfg_name, version).await?;
+        let rows = sqlx::query!(
+            r#"SELECT Deltas.id, Deltas.created_at as "created_at: NaiveDateTime",
+                    Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value"
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE Deltas.base_id = $1
+                    ORDER BY Deltas.id"#,
+            base_id,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut blame = HashMap::<String, BlameEntry>::new();
+        let mut latest = Value::Null;
+        for row in rows {
+            let cfg = build_cfg_from_base_and_delta(row.cfg, row.delta);
+            for (path, value) in leaf_paths(&cfg) {
+                if blame.get(&path).is_some_and(|b| b.value == *value) {
+                    continue;
+                }
+                let entry = BlameEntry {
+                    path: path.clone(),
+                    value: value.clone(),
+                    id: row.id,
+                    created_at: row.created_at,
+                };
+                blame.insert(path, entry);
+            }
+            latest = cfg;
+        }
+        Ok(leaf_paths(&latest)
+            .into_iter()
+            .filter_map(|(path, _)| blame.remove(&path))
+            .collect())
+    }
+    /// Every stored config of a name, across all its versions, whose values
+    /// match a predicate such as `optimizer.lr > 0.01 and model.layers == 12`.
+    ///
+    /// The predicate is evaluated in sqlite on each delta, falling back to its
+    /// base config for keys the delta leaves unchanged. See `query::compile`
+    /// for the syntax. Matches are ordered by delta id.
+    pub async fn query(
+        &self,
+        cfg_name: impl AsRef<str>,
+        predicate: &str,
+    ) -> Result<Vec<HistoryEntry>> {
+        let (condition, binds) = compile(predicate)?;
+        let sql = format!(
+            "SELECT Deltas.id, BaseCfgs.version, Deltas.is_base, Deltas.created_at,
+                    Deltas.delta, BaseCfgs.cfg
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE BaseCfgs.name = ? AND ({})
+                    ORDER BY Deltas.id",
+            condition
+        );
+        let mut query = sqlx::query(&sql).bind(cfg_name.as_ref());
+        for bind in binds {
+            query = match bind {
+                Bind::Int(i) => query.bind(i),
+                Bind::Float(f) => query.bind(f),
+                Bind::Text(s) => query.bind(s),
+            };
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        rows.into_iter()
+            .map(|row| {
+                let Json(delta) = row.try_get("delta")?;
+                let Json(cfg) = row.try_get("cfg")?;
+                Ok(HistoryEntry {
+                    id: row.try_get("id")?,
+                    version: row.try_get("version")?,
+                    is_base: row.try_get("is_base")?,
+                    created_at: row.try_get("created_at")?,
+                    cfg: build_cfg_from_base_and_delta(cfg, delta),
+                })
+            })
+            .collect()
+    }
+    /// Every leaf of every stored config whose `key.path = value` text contains
+    /// `pattern`, ignoring ascii case. Values are matched in their json form, so
+    /// strings are quoted. Hits are ordered by name, delta id and leaf.
+    pub async fn search_text(&self, pattern: &str) -> Result<Vec<TextHit>> {
+        // LIKE on the trigram index narrows the lines, `%` and `_` in the pattern
+        // are wildcards there so the lines are matched literally again below.
+        let like = format!("%{}%", pattern);
+        let rows = sqlx::query!(
+            r#"SELECT BaseCfgs.name, BaseCfgs.version, Deltas.id,
+                    ConfigText.path as "path!: String", ConfigText.line as "line!: String"
+                    FROM ConfigText
+                    INNER JOIN Deltas on Deltas.id = ConfigText.delta_id
+                    INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE ConfigText.line LIKE $1
+                    ORDER BY BaseCfgs.name, Deltas.id, ConfigText.rowid"#,
+            like
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let pattern = pattern.to_ascii_lowercase();
+        Ok(rows
+            .into_iter()
+            .filter(|row| row.line.to_ascii_lowercase().contains(&pattern))
+            .map(|row| {
+                let value = &row.line[row.path.len() + " = ".len()..];
+                TextHit {
+                    name: row.name,
+                    version: row.version,
+                    id: row.id,
+                    value: serde_json::from_str(value).unwrap_or_else(|_| Value::from(value)),
+                    path: row.path,
+                }
+            })
+            .collect())
+    }
+    /// The config name and version a delta was stored under.
+    pub async fn get_delta_origin(&self, delta_id: i64) -> Result<(String, i64)> {
+        let row = sqlx::query!(
+            r#"SELECT BaseCfgs.name, BaseCfgs.version FROM Deltas
+                    INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE Deltas.id = $1"#,
+            delta_id,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(DeltaError::DeltaNotFound(delta_id))?;
+        Ok((row.name, row.version))
+    }
+    /// Record the file a config name is added from, returning the file it was
+    /// previously added from if that's a different one. Two files stored under one
+    /// name mix their histories into one family.
+    pub async fn set_source(
+        &self,
+        cfg_name: impl AsRef<str>,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<PathBuf>> {
+        let cfg_name = cfg_name.as_ref();
+        let path = path.as_ref().canonicalize()?;
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| DeltaError::InvalidPath(path.clone()))?;
+        let mut tx = self.begin_write().await?;
+        let previous =
+            sqlx::query_scalar!("SELECT path FROM ConfigSources WHERE name = $1", cfg_name)
+                .fetch_optional(&mut *tx)
+                .await?;
+        sqlx::query!(
+            "INSERT OR REPLACE INTO ConfigSources (name, path) VALUES ($1, $2)",
+            cfg_name,
+            path_str
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(previous
+            .map(PathBuf::from)
+            .filter(|previous| *previous != path))
+    }
+    /// Whether adding this config would leave the store unchanged, that is
+    /// one of the config's stored deltas rebuilds to exactly this config.
+    pub async fn contains_config(&self, cfg_name: impl AsRef<str>, cfg: &Value) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+        let hash_str = calculate_cfg_hash(cfg)?;
+        let Some((base_id, base_cfg)) =
+            Self::get_base_config_by_hash(&mut conn, cfg_name.as_ref(), &hash_str).await?
+        else {
+            return Ok(false);
+        };
+        let Some(delta) = calculate_delta(&base_cfg, cfg)? else {
+            return Ok(true);
+        };
+        let deltas = sqlx::query_scalar!(
+            r#"SELECT delta as "delta: Value" FROM Deltas WHERE base_id = $1"#,
+            base_id
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(deltas.contains(&delta))
+    }
+    /// Remove one stored config by its delta id.
+    ///
+    /// Removing the base row of a version whose other deltas remain makes the
+    /// next config the version's base, the remaining deltas are rewritten against
+    /// it so no value of the removed config is kept. Removing the last row of a
+    /// version removes the version, its number isn't reused.
+    pub async fn delete_delta(&self, delta_id: i64) -> Result<()> {
+        let mut tx = self.begin_write().await?;
+        Self::delete_deltas(&mut tx, &[delta_id]).await?;
+        Self::purge_text(&mut tx).await?;
+        tx.commit().await?;
+        info!("Deleted delta {}", delta_id);
+        Ok(())
+    }
+    /// Remove delta rows along with their text and tags, then rebase each
+    /// version whose base row was among them.
+    async fn delete_deltas(conn: &mut SqliteConnection, delta_ids: &[i64]) -> Result<()> {
+        let mut rebased = vec![];
+        for &delta_id in delta_ids {
+            let row = sqlx::query!(
+                r#"SELECT base_id, is_base as "is_base: bool" FROM Deltas WHERE id = $1"#,
+                delta_id
+            )
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(DeltaError::DeltaNotFound(delta_id))?;
+            sqlx::query!("DELETE FROM ConfigText WHERE delta_id = $1", delta_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query!("DELETE FROM Tags WHERE delta_id = $1", delta_id)
+                .execute(&mut *conn)
+                .await?;
+            sqlx::query!("DELETE FROM Deltas WHERE id = $1", delta_id)
+                .execute(&mut *conn)
+                .await?;
+            if row.is_base {
+                rebased.push(row.base_id);
+            }
+        }
+        for base_id in rebased {
+            Self::rebase(conn, base_id).await?;
+        }
+        Ok(())
+    }
+    /// Make the oldest remaining delta of a base version its new base, or remove
+    /// the version if it has no deltas left.
+    ///
+    /// Versions written by older builds can hold configs of differing shapes that
+    /// can't be expressed against the new base. Those move to the version of their
+    /// own shape, a new one if the name has none, so no value of the removed config
+    /// is kept in the base.
+    async fn rebase(conn: &mut SqliteConnection, base_id: i64) -> Result<()> {
+        let rows = sqlx::query!(
+            r#"SELECT Deltas.id, Deltas.delta as "delta: Value", BaseCfgs.cfg as "cfg: Value",
+                    BaseCfgs.name
+                    FROM Deltas INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                    WHERE Deltas.base_id = $1
+                    ORDER BY Deltas.id"#,
+            base_id
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let mut rebased = false;
+        for row in rows {
+            let cfg = build_cfg_from_base_and_delta(row.cfg, row.delta);
+            let hash = calculate_cfg_hash(&cfg)?;
+            let marker = Value::Object(Map::new());
+            match Self::get_base_config_by_hash(conn, &row.name, &hash).await? {
+                Some((target_id, target_cfg)) if target_id != base_id || rebased => {
+                    let delta = calculate_delta(&target_cfg, &cfg)?.unwrap_or(marker);
+                    sqlx::query!(
+                        "UPDATE Deltas SET base_id = $1, delta = $2, is_base = FALSE WHERE id = $3",
+                        target_id,
+                        delta,
+                        row.id
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                None if rebased => {
+                    let (target_id, version) =
+                        Self::insert_base(conn, &row.name, &cfg, &hash).await?;
+                    warn!(
+                        "Delta {} differs in shape from its base, moving it to version {}",
+                        row.id, version
+                    );
+                    sqlx::query!(
+                        "UPDATE Deltas SET base_id = $1, delta = $2, is_base = TRUE WHERE id = $3",
+                        target_id,
+                        marker,
+                        row.id
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                _ => {
+                    debug!("Rebasing base {} onto delta {}", base_id, row.id);
+                    sqlx::query!(
+                        "UPDATE BaseCfgs SET cfg = $1, cfg_hash = $2 WHERE id = $3",
+                        cfg,
+                        hash,
+                        base_id
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                    sqlx::query!(
+                        "UPDATE Deltas SET delta = $1, is_base = TRUE WHERE id = $2",
+                        marker,
+                        row.id
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                    rebased = true;
+                }
+            }
+        }
+        if !rebased {
+            sqlx::query!("DELETE FROM BaseCfgs WHERE id = $1", base_id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+    /// Merge the segments of `ConfigText` so the text of removed configs is dropped
+    /// from the index rather than only marked deleted.
+    async fn purge_text(conn: &mut SqliteConnection) -> Result<()> {
+        sqlx::query!("INSERT INTO ConfigText (ConfigText) VALUES ('optimize')")
+            .execute(&mut *conn)
+            .await?;
+        Ok(())
+    }
+    /// Remove a base version of a config along with all its deltas, returning
+    /// how many deltas were removed. Other versions keep their numbers.
+    pub async fn delete_base_version(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: i64,
+    ) -> Result<u64> {
+        let base_id = self.get_base_config_id(cfg_name, Some(version)).await?;
+        let mut tx = self.begin_write().await?;
+        let removed = Self::delete_base(&mut tx, base_id).await?;
+        Self::purge_text(&mut tx).await?;
+        tx.commit().await?;
+        Ok(removed)
+    }
+    /// Remove every version and delta stored under a config name, returning how
+    /// many deltas were removed. Adding the name again continues its version numbers.
+    pub async fn delete_config_family(&self, cfg_name: impl AsRef<str>) -> Result<u64> {
+        let cfg_name = cfg_name.as_ref();
+        let mut tx = self.begin_write().await?;
+        let base_ids = sqlx::query_scalar!("SELECT id FROM BaseCfgs WHERE name = $1", cfg_name)
+            .fetch_all(&mut *tx)
+            .await?;
+        if base_ids.is_empty() {
+            return Err(DeltaError::ConfigNotFound {
+                name: cfg_name.to_string(),
+                version: None,
+                available: self.get_base_configs().await?,
+            });
+        }
+        let mut removed = 0;
+        for base_id in base_ids {
+            removed += Self::delete_base(&mut tx, base_id).await?;
+        }
+        Self::purge_text(&mut tx).await?;
+        tx.commit().await?;
+        Ok(removed)
+    }
+    async fn delete_base(conn: &mut SqliteConnection, base_id: i64) -> Result<u64> {
+        sqlx::query!(
+            "DELETE FROM ConfigText WHERE delta_id IN (SELECT id FROM Deltas WHERE base_id = $1)",
+            base_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM Tags WHERE delta_id IN (SELECT id FROM Deltas WHERE base_id = $1)",
+            base_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        let removed = sqlx::query!("DELETE FROM Deltas WHERE base_id = $1", base_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+        sqlx::query!("DELETE FROM BaseCfgs WHERE id = $1", base_id)
+            .execute(&mut *conn)
+            .await?;
+        info!("Deleted base {} and its {} deltas", base_id, removed);
+        Ok(removed)
+    }
+    /// Label a stored config, eg. with a release name. Tagging twice is a no-op.
+    pub async fn tag_delta(&self, delta_id: i64, tag: &str) -> Result<()> {
+        sqlx::query_scalar!("SELECT id FROM Deltas WHERE id = $1", delta_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DeltaError::DeltaNotFound(delta_id))?;
+        sqlx::query!(
+            "INSERT OR IGNORE INTO Tags (delta_id, tag) VALUES ($1, $2)",
+            delta_id,
+            tag
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    /// Remove a tag from a stored config, returning whether it had the tag.
+    pub async fn untag_delta(&self, delta_id: i64, tag: &str) -> Result<bool> {
+        let removed = sqlx::query!(
+            "DELETE FROM Tags WHERE delta_id = $1 AND tag = $2",
+            delta_id,
+            tag
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(removed > 0)
+    }
+    /// The tags of a stored config, in order.
+    pub async fn get_tags(&self, delta_id: i64) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar!(
+            "SELECT tag FROM Tags WHERE delta_id = $1 ORDER BY tag",
+            delta_id
+        )
+        .fetch_all(&self.pool)
+        .await?)
+    }
+    /// Set the retention policy `gc` applies to a config family, replacing any previous one.
+    pub async fn set_retention(
+        &self,
+        cfg_name: impl AsRef<str>,
+        policy: &RetentionPolicy,
+    ) -> Result<()> {
+        let cfg_name = cfg_name.as_ref();
+        policy.validate()?;
+        sqlx::query!(
+            "INSERT OR REPLACE INTO Retention (name, keep_last, keep_days, keep_tagged)
+                    VALUES ($1, $2, $3, $4)",
+            cfg_name,
+            policy.keep_last,
+            policy.keep_days,
+            policy.keep_tagged
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    /// Remove a family's retention policy, returning whether it had one.
+    /// `gc` then keeps all of its configs.
+    pub async fn clear_retention(&self, cfg_name: impl AsRef<str>) -> Result<bool> {
+        let cfg_name = cfg_name.as_ref();
+        let removed = sqlx::query!("DELETE FROM Retention WHERE name = $1", cfg_name)
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+        Ok(removed > 0)
+    }
+    /// Every retention policy by config name.
+    pub async fn get_retention_policies(&self) -> Result<Vec<(String, RetentionPolicy)>> {
+        let rows = sqlx::query!(
+            r#"SELECT name, keep_last, keep_days, keep_tagged as "keep_tagged: bool"
+                    FROM Retention ORDER BY name"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let count = |name: &str, rule: &str, n: Option<i64>| {
+            n.map(u32::try_from).transpose().map_err(|_| {
+                DeltaError::InvalidPolicy(format!("{} of {} is out of range", rule, name))
+            })
+        };
+        rows.into_iter()
+            .map(|row| {
+                let policy = RetentionPolicy {
+                    keep_last: count(&row.name, "keep_last", row.keep_last)?,
+                    keep_days: count(&row.name, "keep_days", row.keep_days)?,
+                    keep_tagged: row.keep_tagged,
+                };
+                policy.validate()?;
+                Ok((row.name, policy))
+            })
+            .collect()
+    }
+    /// Apply every family's retention policy, collapse runs of identical consecutive
+    /// configs into the oldest one of the run and compact the database.
+    ///
+    /// Tags of collapsed rows move to the row that's kept. The latest config and the
+    /// config at any point in time are unchanged by collapsing.
+    pub async fn gc(&self) -> Result<GcReport> {
+        let mut report = GcReport {
+            size_before: self.database_size().await?,
+            ..Default::default()
+        };
+        let now = Utc::now().naive_utc();
+        let mut expired = vec![];
+        for (cfg_name, policy) in self.get_retention_policies().await? {
+            let history = match self.get_history(&cfg_name).await {
+                Err(DeltaError::ConfigNotFound { .. }) => continue,
+                history => history?,
+            };
+            let tagged = sqlx::query_scalar!(
+                r#"SELECT Tags.delta_id FROM Tags
+                        INNER JOIN Deltas on Tags.delta_id = Deltas.id
+                        INNER JOIN BaseCfgs on Deltas.base_id = BaseCfgs.id
+                        WHERE BaseCfgs.name = $1"#,
+                cfg_name
+            )
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+            let family_expired = policy.expired(&history, &tagged, now);
+            debug!("Expiring {} configs of {}", family_expired.len(), cfg_name);
+            expired.extend(family_expired);
+        }
+        let mut tx = self.begin_write().await?;
+        Self::delete_deltas(&mut tx, &expired).await?;
+        report.expired = expired.len() as u64;
+        // A config identical to the one before it adds nothing, the later rows of such
+        // a run are dropped so every point in the history still has the same config.
+        let names = sqlx::query_scalar!("SELECT DISTINCT name FROM BaseCfgs")
+            .fetch_all(&mut *tx)
+            .await?;
+        let mut duplicates = vec![];
+        for cfg_name in names {
+            let mut history = Self::history(&mut tx, &cfg_name).await?.into_iter();
+            let Some(mut kept) = history.next() else {
+                continue;
+            };
+            for entry in history {
+                if entry.cfg == kept.cfg {
+                    duplicates.push((kept.id, entry.id));
+                } else {
+                    kept = entry;
+                }
+            }
+        }
+        for (kept, id) in &duplicates {
+            sqlx::query!(
+                "UPDATE OR IGNORE Tags SET delta_id = $1 WHERE delta_id = $2",
+                kept,
+                id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        let ids = duplicates.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+        Self::delete_deltas(&mut tx, &ids).await?;
+        report.duplicates = ids.len() as u64;
+        Self::purge_text(&mut tx).await?;
+        tx.commit().await?;
+        sqlx::query!("VACUUM").execute(&self.pool).await?;
+        report.size_after = self.database_size().await?;
+        info!("Collected garbage {:?}", report);
+        Ok(report)
+    }
+    /// Size of the database in bytes, excluding its write ahead log.
+    async fn database_size(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT page_count * page_size as "size!: i64"
+                    FROM pragma_page_count(), pragma_page_size()"#
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+}
+
+/// Blocking wrapper around `AsyncStore`, which drives it on its own runtime.
+/// This panics if called from within an async context, use `AsyncStore` there.
+pub struct Store {
+    inner: AsyncStore,
+    rt: Runtime,
+}
+impl Store {
+    pub fn block_on<F>(&self, f: F) -> F::Output
+    where
+        F: Future,
+    {
+        self.rt.block_on(f)
+    }
+    pub fn new(url: impl AsRef<str>) -> Result<Store> {
+        let rt = Runtime::new()?;
+        let inner = rt.block_on(AsyncStore::new(url))?;
+        Ok(Store { inner, rt })
+    }
+    /// The underlying async store, which shares this store's connection pool.
+    pub fn as_async(&self) -> &AsyncStore {
+        &self.inner
+    }
+    pub fn get_base_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Option<Value>> {
+        self.block_on(self.inner.get_base_config(cfg_name, version))
+    }
+    pub fn add_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        cfg: serde_json::Value,
+    ) -> Result<AddOutcome> {
+        self.block_on(self.inner.add_config(cfg_name, cfg))
+    }
+    /// Add many configs in a single transaction, either all of them are stored or none are.
+    /// The outcomes are in the same order as the configs.
+    pub fn add_configs<S: AsRef<str>>(
+        &self,
+        cfgs: impl IntoIterator<Item = (S, serde_json::Value)>,
+    ) -> Result<Vec<AddOutcome>> {
+        self.block_on(self.inner.add_configs(cfgs))
+    }
+    /// Fetch the most recent config commited at a name and a version. If version is none,
+    /// the newest config across all versions, the one `get_config_at` gives for now.
+    pub fn get_latest_config(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Option<Value>> {
+        self.block_on(self.inner.get_latest_config(cfg_name, version))
+    }
+    /// Get the base configuration types and the number of versions they have
+    pub fn get_base_configs(&self) -> Result<Vec<(String, i64)>> {
+        self.block_on(self.inner.get_base_configs())
+    }
+    /// Id of the base config at a name and version, if version is none, then the latest version.
+    pub fn get_base_config_id(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<i64> {
+        self.block_on(self.inner.get_base_config_id(cfg_name, version))
+    }
+    pub fn get_all_deltas(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<u64>,
+    ) -> Result<Vec<(i64, Value)>> {
+        self.block_on(self.inner.get_all_deltas(cfg_name, version))
+    }
+    pub fn get_delta(&self, delta_id: i64) -> Result<Value> {
+        self.block_on(self.inner.get_delta(delta_id))
+    }
+    /// The config of a name that was current at a moment in UTC, across all its
+    /// versions. None if nothing had been stored under the name by then.
+    pub fn get_config_at(
+        &self,
+        cfg_name: impl AsRef<str>,
+        at: NaiveDateTime,
+    ) -> Result<Option<Value>> {
+        self.block_on(self.inner.get_config_at(cfg_name, at))
+    }
+    /// Every stored config of a name across all its versions, oldest first.
+    pub fn get_history(&self, cfg_name: impl AsRef<str>) -> Result<Vec<HistoryEntry>> {
+        self.block_on(self.inner.get_history(cfg_name))
+    }
+    /// For every leaf of the latest config at a name and version, the delta
+    /// that last set its value. If version is none, then the latest version.
+    pub fn blame(
+        &self,
+        cfg_name: impl AsRef<str>,
+        version: Option<i64>,
+    ) -> Result<Vec<BlameEntry>> {
+        self.block_on(self.inner.blame(cfg_name, version))
+    }
+    /// Every stored config of a name, across all its versions, whose values
+    /// match a predicate such as `optimizer.lr > 0.01 and model.layers == 12`.
+    pub fn query(&self, cfg_name: impl AsRef<str>, predicate: &str) -> Result<Vec<HistoryEntry>> {
+        self.block_on(self.inner.query(cfg_name, predicate))
+    }
+    /// Every leaf of every stored config whose `key.path = value` text contains
+    /// `pattern`, ignoring ascii case.
+    pub fn search_text(&self, pattern: &str) -> Result<Vec<TextHit>> {
+        self.block_on(self.inner.search_text(pattern))
+    }
+    /// The config name and version a delta was stored under.
+    pub fn get_delta_origin(&self, delta_id: i64) -> Result<(String, i64)> {
+        self.block_on(self.inner.get_delta_origin(delta_id))
+    }
+    /// Whether adding this config would leave the store unchanged, that is
+    /// one of the config's stored deltas rebuilds to exactly this config.
+    pub fn set_source(
+        &self,
+        cfg_name: impl AsRef<str>,
+        path: impl AsRef<Path>,
+    ) -> Result<Option<PathBuf>> {
+        self.block_on(self.inner.set_source(cfg_name, path))
+    }
+    pub fn contains_config(&self, cfg_name: impl AsRef<str>, cfg: &Value) -> Result<bool> {
+        self.block_on(self.inner.contains_config(cfg_name, cfg))
+    }
+    /// Remove one stored config by its delta id, see `AsyncStore::delete_delta`.
+    pub fn delete_delta(&self, delta_id: i64) -> Result<()> {
+        self.block_on(self.inner.delete_delta(delta_id))
+    }
+    /// Remove a base version of a config along with all its deltas, returning
+    /// how many deltas were removed. Other versions keep their numbers.
+    pub fn delete_base_version(&self, cfg_name: impl AsRef<str>, version: i64) -> Result<u64> {
+        self.block_on(self.inner.delete_base_version(cfg_name, version))
+    }
+    /// Remove every version and delta stored under a config name, returning how
+    /// many deltas were removed. Adding the name again continues its version numbers.
+    pub fn delete_config_family(&self, cfg_name: impl AsRef<str>) -> Result<u64> {
+        self.block_on(self.inner.delete_config_family(cfg_name))
+    }
+    /// Label a stored config, eg. with a release name. Tagging twice is a no-op.
+    pub fn tag_delta(&self, delta_id: i64, tag: &str) -> Result<()> {
+        self.block_on(self.inner.tag_delta(delta_id, tag))
+    }
+    /// Remove a tag from a stored config, returning whether it had the tag.
+    pub fn untag_delta(&self, delta_id: i64, tag: &str) -> Result<bool> {
+        self.block_on(self.inner.untag_delta(delta_id, tag))
+    }
+    /// The tags of a stored config, in order.
+    pub fn get_tags(&self, delta_id: i64) -> Result<Vec<String>> {
+        self.block_on(self.inner.get_tags(delta_id))
+    }
+    /// Set the retention policy `gc` applies to a config family, replacing any previous one.
+    pub fn set_retention(&self, cfg_name: impl AsRef<str>, policy: &RetentionPolicy) -> Result<()> {
+        self.block_on(self.inner.set_retention(cfg_name, policy))
+    }
+    /// Remove a family's retention policy, returning whether it had one.
+    pub fn clear_retention(&self, cfg_name: impl AsRef<str>) -> Result<bool> {
+        self.block_on(self.inner.clear_retention(cfg_name))
+    }
+    /// Every retention policy by config name.
+    pub fn get_retention_policies(&self) -> Result<Vec<(String, RetentionPolicy)>> {
+        self.block_on(self.inner.get_retention_policies())
+    }
+    /// Apply every family's retention policy, collapse consecutive duplicate configs
+    /// and compact the database, see `AsyncStore::gc`.
+    pub fn gc(&self) -> Result<GcReport> {
+        self.block_on(self.inner.gc())
+    }
+}
+
+#[cfg(test)]
+mod db_tests {
+
+    use super::*;
+    use serde_json::json;
+    fn mock_db() -> Store {
+        let s = Store::new("sqlite::memory:").unwrap();
+        s.block_on(sqlx::migrate!().run(&s.inner.pool)).unwrap();
+        s
+    }
+    fn add_base(s: &Store, cfg_name: &str, cfg: Value) -> i64 {
+        s.block_on(async {
+            let mut conn = s.inner.pool.acquire().await?;
+            AsyncStore::add_base_config(&mut conn, cfg_name, cfg).await
+        })
+        .unwrap()
+    }
+    #[test]
+    fn test_add() {
+        let s = mock_db();
+        let json = serde_json::json!({"test": 200});
+        add_base(&s, "Test", json);
+    }
+    #[test]
+    fn test_add_get() {
+        let s = mock_db();
+        let json = serde_json::json!({"test": 200});
+        add_base(&s, "Test", json.clone());
+        let cfg = s.get_base_config("Test", None).unwrap().unwrap();
+        assert_eq!(cfg, json)
+    }
+    #[test]
+    fn test_add_multi() {
+        let s = mock_db();
+        let mut c: i64 = -1;
+        let mut id = 0;
+        for i in 0..10 {
+            let json = serde_json::json!({format!("test{}",i): 200});
+            c = add_base(&s, "test", json.clone());
+            id = i;
+        }
+        assert_eq!(c, id);
+    }
+    #[test]
+    fn test_read_latest() {
+        let s = mock_db();
+        let json = serde_json::json!({format!("test"): 200});
+        add_base(&s, "test", json.clone());
+        let json2 = serde_json::json!({format!("test"): 200, format!("Test"): 200});
+        add_base(&s, "test", json2.clone());
+        let c = s.get_latest_config("test", None).unwrap().unwrap();
+        assert_eq!(c, json2);
+    }
+    #[test]
+    fn test_insert_read() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        db.add_config("test_ins", json.clone()).unwrap();
+        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
+        assert_eq!(json, json_out);
+    }
+    #[test]
+    fn test_insert_read_version() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"tester": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        db.add_config("test_ins", json.clone()).unwrap();
+        db.add_config("test_ins", json_2.clone()).unwrap();
+        let json_out = db.get_latest_config("test_ins", Some(1)).unwrap().unwrap();
+        assert_eq!(json_2, json_out);
+    }
+    #[test]
+    fn test_read_from_delta() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        db.add_config("test_ins", json.clone()).unwrap();
+        db.add_config("test_ins", json_2.clone()).unwrap();
+        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
+        assert_eq!(json_2, json_out);
+    }
+    #[test]
+    fn test_migrate_legacy_hash() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        db.block_on(
+            sqlx::query(
+                "INSERT INTO BaseCfgs (name, cfg, version, cfg_hash) VALUES ('test_ins', $1, 0, '1234')",
+            )
+            .bind(&json)
+            .execute(&db.inner.pool),
+        )
+        .unwrap();
+        db.block_on(
+            sqlx::query(
+                "INSERT INTO Deltas (base_id, delta, is_base)
+                SELECT id, '{}', TRUE FROM BaseCfgs WHERE cfg_hash = '1234'",
+            )
+            .execute(&db.inner.pool),
+        )
+        .unwrap();
+        db.block_on(db.inner.migrate_cfg_hashes()).unwrap();
+        db.add_config("test_ins", json_2.clone()).unwrap();
+        assert_eq!(db.get_base_configs().unwrap().len(), 1);
+        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
+        assert_eq!(json_2, json_out);
+    }
+    #[test]
+    fn test_read_null_from_delta() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": null}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": null, "duper": 0}, "deep": 0}});
+        db.add_config("test_ins", json.clone()).unwrap();
+        assert_eq!(
+            json,
+            db.get_latest_config("test_ins", None).unwrap().unwrap()
+        );
+        db.add_config("test_ins", json_2.clone()).unwrap();
+        let json_out = db.get_latest_config("test_ins", None).unwrap().unwrap();
+        assert_eq!(json_2, json_out);
+    }
+    #[test]
+    fn test_same_shape_different_names() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        db.add_config("a.yaml", json.clone()).unwrap();
+        db.add_config("b.yaml", json_2.clone()).unwrap();
+        db.add_config("b.yaml", json.clone()).unwrap();
+        assert_eq!(json, db.get_latest_config("a.yaml", None).unwrap().unwrap());
+        assert_eq!(json, db.get_latest_config("b.yaml", None).unwrap().unwrap());
+        assert_eq!(db.get_all_deltas("a.yaml", None).unwrap().len(), 1);
+        assert_eq!(db.get_all_deltas("b.yaml", None).unwrap().len(), 2);
+        assert_eq!(db.get_base_configs().unwrap().len(), 2);
+    }
+    #[test]
+    fn test_add_configs() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        db.add_configs([
+            ("a.yaml", json.clone()),
+            ("a.yaml", json_2.clone()),
+            ("b.yaml", json),
+        ])
+        .unwrap();
+        assert_eq!(
+            json_2,
+            db.get_latest_config("a.yaml", None).unwrap().unwrap()
+        );
+        assert_eq!(db.get_all_deltas("a.yaml", None).unwrap().len(), 2);
+        assert_eq!(db.get_base_configs().unwrap().len(), 2);
+    }
+    #[test]
+    fn test_add_outcome() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        let json_3 = json!({"tester": 0});
+        let AddOutcome::NewBase { version: 0 } = db.add_config("a.yaml", json.clone()).unwrap()
+        else {
+            panic!("Expected a new base.");
+        };
+        let AddOutcome::NewDelta { id } = db.add_config("a.yaml", json_2.clone()).unwrap() else {
+            panic!("Expected a new delta.");
+        };
+        assert_eq!(json_2, db.get_delta(id).unwrap());
+        let AddOutcome::Unchanged { existing_id } =
+            db.add_config("a.yaml", json_2.clone()).unwrap()
+        else {
+            panic!("Expected no change.");
+        };
+        assert_eq!(existing_id, id);
+        let AddOutcome::NewDelta { id: reverted } = db.add_config("a.yaml", json.clone()).unwrap()
+        else {
+            panic!("Expected reverting to the base to be a new delta.");
+        };
+        assert_eq!(json, db.get_delta(reverted).unwrap());
+        assert_eq!(
+            Some(json.clone()),
+            db.get_latest_config("a.yaml", None).unwrap()
+        );
+        assert_eq!(
+            db.add_config("a.yaml", json.clone()).unwrap(),
+            AddOutcome::Unchanged {
+                existing_id: reverted
+            }
+        );
+        // Going back to an older shape is a change even though that version's
+        // latest config matches.
+        assert_eq!(
+            db.add_config("a.yaml", json_3.clone()).unwrap(),
+            AddOutcome::NewBase { version: 1 }
+        );
+        let AddOutcome::NewDelta { id: back } = db.add_config("a.yaml", json.clone()).unwrap()
+        else {
+            panic!("Expected going back to the old shape to be a new delta.");
+        };
+        let history = db.get_history("a.yaml").unwrap();
+        assert_eq!(history.last().map(|e| (e.id, &e.cfg)), Some((back, &json)));
+        assert_eq!(
+            db.get_latest_config("a.yaml", None).unwrap(),
+            Some(json.clone())
+        );
+        assert_eq!(
+            db.get_latest_config("a.yaml", Some(1)).unwrap(),
+            Some(json_3.clone())
+        );
+        assert_eq!(
+            db.add_config("a.yaml", json.clone()).unwrap(),
+            AddOutcome::Unchanged { existing_id: back }
+        );
+    }
+    #[test]
+    fn test_add_file_reverted_to_old_shape() {
+        // Watch re-reads a saved file and adds it, a save going back to an earlier
+        // shape must be recorded like any other change.
+        let db = mock_db();
+        let formats = crate::FormatRegistry::default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.yaml");
+        let mut outcomes = vec![];
+        for contents in ["port: 80\n", "port: 80\ntls: true\n", "port: 80\n"] {
+            std::fs::write(&path, contents).unwrap();
+            let cfg = formats.read_file(&path).unwrap();
+            outcomes.push(db.add_config("app.yaml", cfg).unwrap());
+        }
+        assert!(matches!(
+            outcomes[..],
+            [
+                AddOutcome::NewBase { version: 0 },
+                AddOutcome::NewBase { version: 1 },
+                AddOutcome::NewDelta { .. }
+            ]
+        ));
+        let history = db.get_history("app.yaml").unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].cfg, json!({"port": 80}));
+    }
+    #[test]
+    fn test_set_source() {
+        let db = mock_db();
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        for d in [&a, &b] {
+            std::fs::create_dir(d).unwrap();
+            std::fs::write(d.join("config.yaml"), "port: 80").unwrap();
+        }
+        assert_eq!(
+            db.set_source("config.yaml", a.join("config.yaml")).unwrap(),
+            None
+        );
+        assert_eq!(
+            db.set_source("config.yaml", a.join("../a/./config.yaml"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.set_source("config.yaml", b.join("config.yaml")).unwrap(),
+            Some(a.join("config.yaml").canonicalize().unwrap())
+        );
+        assert!(db
+            .set_source("other.yaml", dir.path().join("missing"))
+            .is_err());
+    }
+    #[test]
+    fn test_add_configs_is_atomic() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let res = db.add_configs([
+            ("a.yaml", json.clone()),
+            ("b.yaml", json!(["not", "a", "tree"])),
+        ]);
+        assert!(res.is_err());
+        assert!(db.get_base_configs().unwrap().is_empty());
+        assert!(db.get_latest_config("a.yaml", None).unwrap().is_none());
+    }
+    #[test]
+    fn test_not_found_errors() {
+        let db = mock_db();
+        db.add_config("a.yaml", json!({"test": 0})).unwrap();
+        let Err(DeltaError::ConfigNotFound {
+            name,
+            version,
+            available,
+        }) = db.get_all_deltas("b.yaml", Some(3))
+        else {
+            panic!("Expected config not found.");
+        };
+        assert_eq!(name, "b.yaml");
+        assert_eq!(version, Some(3));
+        assert_eq!(available, vec![("a.yaml".to_string(), 0)]);
+        assert!(matches!(
+            db.get_delta(100),
+            Err(DeltaError::DeltaNotFound(100))
+        ));
+    }
+    #[test]
+    fn test_contains_config() {
+        let db = mock_db();
+        let json = json!({"test": {"super": 0, "duper": [0, 1]}});
+        let json_2 = json!({"test": {"super": 1, "duper": [0, 2]}});
+        let json_3 = json!({"test": {"super": 2, "duper": [0, 2]}});
+        assert!(!db.contains_config("a.yaml", &json).unwrap());
+        db.add_config("a.yaml", json.clone()).unwrap();
+        let AddOutcome::NewDelta { id } = db.add_config("a.yaml", json_2.clone()).unwrap() else {
+            panic!("Expected a new delta.");
+        };
+        assert!(db.contains_config("a.yaml", &json).unwrap());
+        assert!(db.contains_config("a.yaml", &json_2).unwrap());
+        assert!(!db.contains_config("a.yaml", &json_3).unwrap());
+        assert!(!db.contains_config("b.yaml", &json_2).unwrap());
+        assert!(!db.contains_config("a.yaml", &json!({"other": 0})).unwrap());
+        assert_eq!(
+            db.get_delta_origin(id).unwrap(),
+            (String::from("a.yaml"), 0)
+        );
+        assert!(matches!(
+            db.get_delta_origin(100),
+            Err(DeltaError::DeltaNotFound(100))
+        ));
+    }
+    #[test]
+    fn test_config_at() {
+        let db = mock_db();
+        let cfgs = [json!({"a": 0}), json!({"a": 1}), json!({"b": 0})];
+        let times = [
+            "2026-09-01 12:00:00",
+            "2026-09-01 14:00:00",
+            "2026-09-02 09:30:00",
+        ];
+        for (cfg, time) in cfgs.iter().zip(times) {
+            db.add_config("a.yaml", cfg.clone()).unwrap();
+            db.block_on(
+                sqlx::query!(
+                    "UPDATE Deltas SET created_at = $1 WHERE id = (SELECT MAX(id) FROM Deltas)",
+                    time
+                )
+                .execute(&db.inner.pool),
+            )
+            .unwrap();
+        }
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            db.get_config_at("a.yaml", at("2026-09-01 11:59:59"))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.get_config_at("a.yaml", at("2026-09-01 12:00:00"))
+                .unwrap(),
+            Some(cfgs[0].clone())
+        );
+        assert_eq!(
+            db.get_config_at("a.yaml", at("2026-09-01 14:03:00"))
+                .unwrap(),
+            Some(cfgs[1].clone())
+        );
+        assert_eq!(
+            db.get_config_at("a.yaml", at("2026-10-01 00:00:00"))
+                .unwrap(),
+            Some(cfgs[2].clone())
+        );
+        assert_eq!(
+            db.get_config_at("b.yaml", at("2026-10-01 00:00:00"))
+                .unwrap(),
+            None
+        );
+    }
+    #[test]
+    fn test_config_at_now_is_latest() {
+        let db = mock_db();
+        for cfg in [
+            json!({"port": 80}),
+            json!({"port": 80, "tls": true}),
+            json!({"port": 81}),
+            json!({"port": 81, "tls": false}),
+            json!({"port": 80}),
+        ] {
+            db.add_config("a.yaml", cfg.clone()).unwrap();
+            let now = Utc::now().naive_utc();
+            assert_eq!(db.get_config_at("a.yaml", now).unwrap(), Some(cfg.clone()));
+            assert_eq!(db.get_latest_config("a.yaml", None).unwrap(), Some(cfg));
+        }
+    }
+    #[test]
+    fn test_history() {
+        let db = mock_db();
+        let json = json!({"test": 0});
+        let json_2 = json!({"test": 1});
+        let json_3 = json!({"other": 0});
+        for cfg in [&json, &json_2, &json_3, &json] {
+            db.add_config("a.yaml", cfg.clone()).unwrap();
+        }
+        db.add_config("b.yaml", json.clone()).unwrap();
+        let history = db.get_history("a.yaml").unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|e| (e.version, e.is_base, e.cfg.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, true, json.clone()),
+                (0, false, json_2),
+                (1, true, json_3),
+                (0, false, json)
+            ]
+        );
+        assert!(history.windows(2).all(|w| w[0].id < w[1].id));
+        // The newest config is the one added last, not the highest version's.
+        assert_eq!(
+            db.get_latest_config("a.yaml", None).unwrap().as_ref(),
+            history.last().map(|e| &e.cfg)
+        );
+        assert!(matches!(
+            db.get_history("c.yaml"),
+            Err(DeltaError::ConfigNotFound { .. })
+        ));
+    }
+    #[test]
+    fn test_blame() {
+        let db = mock_db();
+        let cfgs = [
+            json!({"a": 0, "b": {"c": [1, 2]}}),
+            json!({"a": 1, "b": {"c": [1, 2]}}),
+            json!({"a": 1, "b": {"c": [1, 3]}}),
+            json!({"a": 0, "b": {"c": [1, 3]}}),
+        ];
+        let ids = cfgs
+            .iter()
+            .map(|cfg| match db.add_config("a.yaml", cfg.clone()).unwrap() {
+                AddOutcome::NewBase { .. } => db.get_all_deltas("a.yaml", None).unwrap()[0].0,
+                AddOutcome::NewDelta { id } => id,
+                AddOutcome::Unchanged { existing_id } => existing_id,
+            })
+            .collect::<Vec<_>>();
+        db.add_config("a.yaml", json!({"other": 0})).unwrap();
+        let blame = db.blame("a.yaml", Some(0)).unwrap();
+        assert_eq!(
+            blame
+                .iter()
+                .map(|b| (b.path.as_str(), b.value.clone(), b.id))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", json!(0), ids[3]),
+                ("b.c[0]", json!(1), ids[0]),
+                ("b.c[1]", json!(3), ids[2]),
+            ]
+        );
+        assert_eq!(db.blame("a.yaml", None).unwrap()[0].path, "other");
+        assert!(matches!(
+            db.blame("a.yaml", Some(2)),
+            Err(DeltaError::ConfigNotFound { .. })
+        ));
+    }
+    #[test]
+    fn test_query() {
+        let db = mock_db();
+        let layers = |n: &[i64]| Value::from_iter(n.iter().map(|n| json!({"n": n})));
+        let cfgs = [
+            json!({"opt": {"lr": 0.1}, "layers": layers(&[1, 2, 3]), "tag": "a", "on": true}),
+            json!({"opt": {"lr": 0.001}, "layers": layers(&[1, 5, 3]), "tag": "b", "on": true}),
+            json!({"opt": {"lr": 0.1}, "layers": layers(&[1, 2, 3, 4]), "tag": "a", "on": false}),
+            json!({"opt": {"lr": 0.1}, "layers": layers(&[9, 9, 9]), "tag": "a", "on": null}),
+            json!({"opt": {"lr": 0.1}, "layers": layers(&[1, 2]), "tag": "a", "on": true}),
+            json!({"opt": {"lr": 0.5, "decay": 0}, "layers": layers(&[7]), "tag": "c"}),
+        ];
+        for cfg in &cfgs {
+            db.add_config("a.yaml", cfg.clone()).unwrap();
+        }
+        db.add_config("b.yaml", cfgs[1].clone()).unwrap();
+        let query = |predicate: &str| {
+            db.query("a.yaml", predicate)
+                .unwrap()
+                .iter()
+                .map(|e| cfgs.iter().position(|c| *c == e.cfg).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(query("opt.lr > 0.01"), vec![0, 2, 3, 4, 5]);
+        assert_eq!(query("opt.lr < 0.01 or tag == c"), vec![1, 5]);
+        assert_eq!(query("layers[1].n == 5"), vec![1]);
+        assert_eq!(query("layers[1].n == 2"), vec![0, 2, 4]);
+        assert_eq!(query("layers[0].n >= 9 OR layers[3].n == 4"), vec![2, 3]);
+        assert_eq!(query("layers[2].n == 3"), vec![0, 1, 2]);
+        assert_eq!(query("on == true and not tag != 'a'"), vec![0, 4]);
+        assert_eq!(query("on == false or on == null"), vec![2, 3]);
+        assert_eq!(query("on != true"), vec![2, 3, 5]);
+        assert_eq!(query("opt.decay == 0"), vec![5]);
+        assert!(db.query("c.yaml", "tag == a").unwrap().is_empty());
+        assert!(matches!(
+            db.query("a.yaml", "tag =="),
+            Err(DeltaError::InvalidQuery(_))
+        ));
+    }
+    #[test]
+    fn test_search_text() {
+        let db = mock_db();
+        db.add_config("a.yaml", json!({"db": {"host": "db.Example.com"}, "n": 1}))
+            .unwrap();
+        db.add_config("a.yaml", json!({"db": {"host": "localhost"}, "n": 1}))
+            .unwrap();
+        db.add_config(
+            "b.yaml",
+            json!({"hosts": ["example.com", "x"], "example_flag": true}),
+        )
+        .unwrap();
+        let hits = |pattern: &str| {
+            db.search_text(pattern)
+                .unwrap()
+                .into_iter()
+                .map(|h| (h.name, h.version, h.path, h.value))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            (
+                String::from("a.yaml"),
+                0,
+                String::from("db.host"),
+                json!("db.Example.com"),
+            ),
+            (
+                String::from("b.yaml"),
+                0,
+                String::from("hosts[0]"),
+                json!("example.com"),
+            ),
+        ];
+        assert_eq!(hits("EXAMPLE.COM"), expected);
+        assert_eq!(hits("example_f").len(), 1);
+        assert_eq!(hits("e%c").len(), 0);
+        assert_eq!(hits("n = 1").len(), 2);
+        assert_eq!(hits("x").len(), 4);
+        // Configs stored without their text are indexed when the store opens.
+        db.block_on(sqlx::query!("DELETE FROM ConfigText").execute(&db.inner.pool))
+            .unwrap();
+        db.block_on(sqlx::query!("UPDATE Deltas SET text_indexed = FALSE").execute(&db.inner.pool))
+            .unwrap();
+        assert!(hits("example").is_empty());
+        db.block_on(db.inner.index_missing_text()).unwrap();
+        assert_eq!(hits("EXAMPLE.COM"), expected);
+    }
+    #[test]
+    fn test_delete_delta() {
+        let db = mock_db();
+        let cfgs = [
+            json!({"token": "secret", "n": 0}),
+            json!({"token": "t1", "n": 1}),
+            json!({"token": "t2", "n": 1}),
+        ];
+        for cfg in &cfgs {
+            db.add_config("a.yaml", cfg.clone()).unwrap();
+        }
+        let ids = |db: &Store| {
+            db.get_history("a.yaml")
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.id, e.is_base, e.cfg))
+                .collect::<Vec<_>>()
+        };
+        let history = ids(&db);
+        db.delete_delta(history[0].0).unwrap();
+        assert_eq!(
+            ids(&db),
+            vec![
+                (history[1].0, true, cfgs[1].clone()),
+                (history[2].0, false, cfgs[2].clone())
+            ]
+        );
+        assert_eq!(
+            db.get_base_config("a.yaml", Some(0)).unwrap(),
+            Some(cfgs[1].clone())
+        );
+        assert!(db.search_text("secret").unwrap().is_empty());
+        assert!(matches!(
+            db.delete_delta(history[0].0),
+            Err(DeltaError::DeltaNotFound(_))
+        ));
+        db.delete_delta(history[2].0).unwrap();
+        assert_eq!(ids(&db), vec![(history[1].0, true, cfgs[1].clone())]);
+        db.delete_delta(history[1].0).unwrap();
+        assert!(db.get_base_configs().unwrap().is_empty());
+        assert!(db.search_text("t1").unwrap().is_empty());
+    }
+    #[test]
+    fn test_delete_mixed_shape_base() {
+        let db = mock_db();
+        db.add_config("a.yaml", json!({"token": "secret", "xs": [1]}))
+            .unwrap();
+        // Older builds ignored array elements in shapes, so one version could hold both.
+        for delta in [
+            json!({"token": "t1", "xs": [{"b": 2}]}),
+            json!({"token": "t2"}),
+        ] {
+            db.block_on(
+                sqlx::query("INSERT INTO Deltas (base_id, delta) SELECT id, $1 FROM BaseCfgs")
+                    .bind(&delta)
+                    .execute(&db.inner.pool),
+            )
+            .unwrap();
+        }
+        let history = db.get_history("a.yaml").unwrap();
+        db.delete_delta(history[0].id).unwrap();
+        assert_eq!(
+            db.get_history("a.yaml")
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.id, e.version, e.is_base, e.cfg))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    history[1].id,
+                    0,
+                    true,
+                    json!({"token": "t1", "xs": [{"b": 2}]})
+                ),
+                (history[2].id, 1, true, json!({"token": "t2", "xs": [1]}))
+            ]
+        );
+        let stored = db
+            .block_on(
+                sqlx::query_scalar::<_, i64>(
+                    "SELECT COUNT(*) FROM BaseCfgs WHERE cfg LIKE '%secret%'
+                        UNION ALL SELECT COUNT(*) FROM Deltas WHERE delta LIKE '%secret%'",
+                )
+                .fetch_all(&db.inner.pool),
+            )
+            .unwrap();
+        assert_eq!(stored, vec![0, 0]);
+        assert!(db.search_text("secret").unwrap().is_empty());
+        assert_eq!(
+            db.add_config("a.yaml", json!({"token": "t3", "xs": [2]}))
+                .unwrap(),
+            AddOutcome::NewDelta {
+                id: history[2].id + 1
+            }
+        );
+    }
+    #[test]
+    fn test_delete_versions() {
+        let db = mock_db();
+        db.add_config("a.yaml", json!({"a": 0})).unwrap();
+        db.add_config("a.yaml", json!({"a": 1})).unwrap();
+        db.add_config("a.yaml", json!({"b": 0})).unwrap();
+        db.add_config("b.yaml", json!({"a": 0})).unwrap();
+        assert_eq!(db.delete_base_version("a.yaml", 0).unwrap(), 2);
+        assert!(matches!(
+            db.delete_base_version("a.yaml", 0),
+            Err(DeltaError::ConfigNotFound { .. })
+        ));
+        // Numbers of removed versions aren't reused, even the newest one's.
+        assert_eq!(
+            db.add_config("a.yaml", json!({"c": 0})).unwrap(),
+            AddOutcome::NewBase { version: 2 }
+        );
+        assert_eq!(db.delete_base_version("a.yaml", 2).unwrap(), 1);
+        assert_eq!(
+            db.add_config("a.yaml", json!({"c": 0})).unwrap(),
+            AddOutcome::NewBase { version: 3 }
+        );
+        assert_eq!(db.delete_base_version("a.yaml", 3).unwrap(), 1);
+        assert_eq!(
+            db.add_config("a.yaml", json!({"a": 0})).unwrap(),
+            AddOutcome::NewBase { version: 4 }
+        );
+        assert_eq!(
+            db.get_latest_config("a.yaml", Some(1)).unwrap(),
+            Some(json!({"b": 0}))
+        );
+        assert_eq!(db.delete_config_family("a.yaml").unwrap(), 2);
+        assert!(matches!(
+            db.delete_config_family("a.yaml"),
+            Err(DeltaError::ConfigNotFound { .. })
+        ));
+        assert_eq!(
+            db.get_base_configs().unwrap(),
+            vec![(String::from("b.yaml"), 0)]
+        );
+        assert_eq!(db.search_text("a").unwrap().len(), 1);
+        assert_eq!(
+            db.add_config("a.yaml", json!({"a": 0})).unwrap(),
+            AddOutcome::NewBase { version: 5 }
+        );
+    }
+    #[test]
+    fn test_gc() {
+        let db = mock_db();
+        for n in [0, 1, 2, 1, 3, 4] {
+            db.add_config("a.yaml", json!({"n": n})).unwrap();
+        }
+        for n in [0, 5, 5] {
+            db.add_config("b.yaml", json!({"n": n})).unwrap();
+        }
+        db.block_on(
+            sqlx::query!("UPDATE Deltas SET created_at = '2020-01-01 00:00:00'")
+                .execute(&db.inner.pool),
+        )
+        .unwrap();
+        let ids = |name: &str| {
+            db.get_history(name)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.id, e.cfg))
+                .collect::<Vec<_>>()
+        };
+        let a = ids("a.yaml");
+        db.tag_delta(a[1].0, "release").unwrap();
+        db.tag_delta(a[3].0, "rc").unwrap();
+        db.tag_delta(a[3].0, "release").unwrap();
+        db.tag_delta(a[3].0, "release").unwrap();
+        assert_eq!(db.get_tags(a[3].0).unwrap(), vec!["rc", "release"]);
+        assert!(matches!(
+            db.tag_delta(1000, "x"),
+            Err(DeltaError::DeltaNotFound(1000))
+        ));
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            keep_days: None,
+            keep_tagged: true,
+        };
+        db.set_retention("a.yaml", &policy).unwrap();
+        assert!(matches!(
+            db.set_retention(
+                "b.yaml",
+                &RetentionPolicy {
+                    keep_last: Some(0),
+                    ..Default::default()
+                }
+            ),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        assert!(matches!(
+            db.set_retention(
+                "b.yaml",
+                &RetentionPolicy {
+                    keep_tagged: true,
+                    ..Default::default()
+                }
+            ),
+            Err(DeltaError::InvalidPolicy(_))
+        ));
+        db.set_retention(
+            "gone.yaml",
+            &RetentionPolicy {
+                keep_days: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(db.get_retention_policies().unwrap().len(), 2);
+        assert!(db.clear_retention("gone.yaml").unwrap());
+        assert!(!db.clear_retention("gone.yaml").unwrap());
+        let report = db.gc().unwrap();
+        // a.yaml keeps the tagged {"n": 1} twice and the last two, the second
+        // {"n": 1} is then a duplicate of the first, which becomes the base.
+        // b.yaml has no policy, and its repeated {"n": 5} was never stored.
+        assert_eq!(report.expired, 2);
+        assert_eq!(report.duplicates, 1);
+        assert!(report.size_after > 0);
+        assert_eq!(
+            ids("a.yaml"),
+            vec![a[1].clone(), a[4].clone(), a[5].clone()]
+        );
+        assert_eq!(
+            db.get_base_config("a.yaml", Some(0)).unwrap(),
+            Some(json!({"n": 1}))
+        );
+        assert_eq!(db.get_tags(a[1].0).unwrap(), vec!["rc", "release"]);
+        assert_eq!(ids("b.yaml").len(), 2);
+        assert_eq!(
+            db.gc().unwrap(),
+            GcReport {
+                size_before: report.size_after,
+                size_after: report.size_after,
+                ..Default::default()
+            }
+        );
+    }
+    #[test]
+    fn test_gc_keeps_current_config() {
+        let db = mock_db();
+        for n in [1, 2, 1, 3, 1] {
+            db.add_config("a.yaml", json!({"n": n})).unwrap();
+        }
+        db.block_on(
+            sqlx::query!(
+                "UPDATE Deltas SET created_at = datetime('2020-01-01', '+' || id || ' days')"
+            )
+            .execute(&db.inner.pool),
+        )
+        .unwrap();
+        let history = db.get_history("a.yaml").unwrap();
+        // Removing {"n": 3} leaves the last two configs identical.
+        db.delete_delta(history[3].id).unwrap();
+        let times = history
+            .iter()
+            .flat_map(|e| [e.created_at, e.created_at + chrono::Duration::hours(1)])
+            .collect::<Vec<_>>();
+        let snapshot = |db: &Store| {
+            let at = times
+                .iter()
+                .map(|t| db.get_config_at("a.yaml", *t).unwrap())
+                .collect::<Vec<_>>();
+            (db.get_latest_config("a.yaml", None).unwrap(), at)
+        };
+        let before = snapshot(&db);
+        let report = db.gc().unwrap();
+        assert_eq!(report.duplicates, 1);
+        assert_eq!(snapshot(&db), before);
+        assert_eq!(before.0, Some(json!({"n": 1})));
+        assert_eq!(
+            db.get_history("a.yaml")
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect::<Vec<_>>(),
+            vec![history[0].id, history[1].id, history[2].id]
+        );
+    }
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!(
+            "sqlite://{}?mode=rwc",
+            dir.path().join("delta.db").display()
+        );
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let stores = [
+                AsyncStore::new(&url).await.unwrap(),
+                AsyncStore::new(&url).await.unwrap(),
+            ];
+            let adds = (0..40)
+                .map(|i| {
+                    let s = stores[i % 2].clone();
+                    tokio::spawn(async move { s.add_config("a.yaml", json!({"n": i})).await })
+                })
+                .collect::<Vec<_>>();
+            for add in adds {
+                add.await.unwrap().unwrap();
+            }
+            assert_eq!(stores[0].get_history("a.yaml").await.unwrap().len(), 40);
+        });
+    }
+    #[test]
+    fn test_read_all() {
+        let db = mock_db();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"tester": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        db.add_config("test_ins", json.clone()).unwrap();
+        db.add_config("test_ins", json_2.clone()).unwrap();
+        let cfgs = db.get_base_configs().unwrap();
+        assert_eq!(cfgs.len(), 2)
+    }
+    #[tokio::test]
+    async fn test_async_store_in_runtime() {
+        let s = AsyncStore::new("sqlite::memory:").await.unwrap();
+        let json = json!({"test": {"really": {"super": 0, "duper": 0}, "deep": 0}});
+        let json_2 = json!({"test": {"really": {"super": 1, "duper": 0}, "deep": 1}});
+        s.add_config("test_ins", json.clone()).await.unwrap();
+        s.add_config("test_ins", json_2.clone()).await.unwrap();
+        let json_out = s
+            .get_latest_config("test_ins", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(json_2, json_out);
+        let deltas = s.get_all_deltas("test_ins", None).await.unwrap();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(json_2, s.get_delta(deltas[1].0).await.unwrap());
+        assert_eq!(s.get_base_configs().await.unwrap().len(), 1);
+    }
+    #[tokio::test]
+    async fn test_async_store_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let s = AsyncStore::new("sqlite::memory:").await.unwrap();
+        let add = s.add_config("test_ins", json!({"test": 0}));
+        assert_send(&add);
+        add.await.unwrap();
+        let get = s.get_latest_config("test_ins", None);
+        assert_send(&get);
+        assert_eq!(get.await.unwrap().unwrap(), json!({"test": 0}));
+    }
+}
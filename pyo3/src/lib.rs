@@ -1,17 +1,132 @@
-use anyhow::anyhow;
-use delta_backend::{read_file, Store};
-use pyo3::{pyclass, pymethods, pymodule, types::PyModule, Bound, PyObject, PyResult};
-use std::path::PathBuf;
+use delta_backend::{
+    build_cfg_from_base_and_delta, AddOutcome, DeltaError, FormatRegistry, Naming, Store,
+};
+use pyo3::{
+    exceptions::{PyUserWarning, PyValueError},
+    pyclass, pymethods, pymodule,
+    types::{PyAnyMethods, PyModule},
+    Bound, PyErr, PyObject, PyResult, Python,
+};
+use serde_json::Value;
+use std::{io, path::PathBuf};
 #[pymodule]
 fn delta_db(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ConfigStore>()?;
+    let py = m.py();
+    m.add("DeltaError", py.get_type_bound::<exceptions::DeltaError>())?;
+    m.add(
+        "NotFoundError",
+        py.get_type_bound::<exceptions::NotFoundError>(),
+    )?;
+    m.add(
+        "UnsupportedFormatError",
+        py.get_type_bound::<exceptions::UnsupportedFormatError>(),
+    )?;
+    m.add("ParseError", py.get_type_bound::<exceptions::ParseError>())?;
+    m.add(
+        "InvalidQueryError",
+        py.get_type_bound::<exceptions::InvalidQueryError>(),
+    )?;
+    m.add(
+        "ShapeMismatchError",
+        py.get_type_bound::<exceptions::ShapeMismatchError>(),
+    )?;
+    m.add(
+        "InvalidRootTypeError",
+        py.get_type_bound::<exceptions::InvalidRootTypeError>(),
+    )?;
+    m.add(
+        "DatabaseError",
+        py.get_type_bound::<exceptions::DatabaseError>(),
+    )?;
     Ok(())
 }
 
+mod exceptions {
+    use pyo3::{create_exception, exceptions::PyException};
+    create_exception!(
+        delta_db,
+        DeltaError,
+        PyException,
+        "Base class of delta_db errors."
+    );
+    create_exception!(
+        delta_db,
+        NotFoundError,
+        DeltaError,
+        "No config or delta matches the name, version or id."
+    );
+    create_exception!(
+        delta_db,
+        UnsupportedFormatError,
+        DeltaError,
+        "The config file's format isn't supported."
+    );
+    create_exception!(
+        delta_db,
+        ParseError,
+        DeltaError,
+        "The config file couldn't be parsed."
+    );
+    create_exception!(
+        delta_db,
+        InvalidQueryError,
+        DeltaError,
+        "A config path, path pattern or query is invalid."
+    );
+    create_exception!(
+        delta_db,
+        ShapeMismatchError,
+        DeltaError,
+        "Configs with different key structures were compared."
+    );
+    create_exception!(
+        delta_db,
+        InvalidRootTypeError,
+        DeltaError,
+        "The config isn't a mapping at its root."
+    );
+    create_exception!(
+        delta_db,
+        DatabaseError,
+        DeltaError,
+        "The sqlite database failed."
+    );
+}
+
+/// Convert a backend error into the matching python exception.
+fn to_py_err(e: DeltaError) -> PyErr {
+    let msg = e.to_string();
+    match e {
+        DeltaError::ConfigNotFound { .. } | DeltaError::DeltaNotFound(_) => {
+            exceptions::NotFoundError::new_err(msg)
+        }
+        DeltaError::UnsupportedFormat(_) => exceptions::UnsupportedFormatError::new_err(msg),
+        DeltaError::InvalidPath(_) | DeltaError::InvalidQuery(_) => {
+            exceptions::InvalidQueryError::new_err(msg)
+        }
+        DeltaError::InvalidPattern { source, .. } => {
+            exceptions::InvalidQueryError::new_err(format!("{} {}", msg, source))
+        }
+        DeltaError::Parse { source, .. } | DeltaError::Render { source, .. } => {
+            exceptions::ParseError::new_err(format!("{} {}", msg, source))
+        }
+        DeltaError::InvalidPolicy(_) => exceptions::DeltaError::new_err(msg),
+        DeltaError::ShapeMismatch => exceptions::ShapeMismatchError::new_err(msg),
+        DeltaError::InvalidRootType => exceptions::InvalidRootTypeError::new_err(msg),
+        DeltaError::Database(_) | DeltaError::Migration(_) => {
+            exceptions::DatabaseError::new_err(msg)
+        }
+        // pyo3 picks the OSError subclass of the kind, eg. FileNotFoundError.
+        DeltaError::Io(e) => e.into(),
+    }
+}
+
 /// The config store class provides configuration storage and search.
 /// The configurations are stored in a sqlite database.
 ///
-/// The config store stores config files, (yaml, yml and Json). If the file structure is changed,
+/// The config store stores config files, (yaml, yml, json, json5, jsonc, toml, .env and
+/// .properties). If the file structure is changed,
 /// then the config's version is bumped.
 ///
 /// The config store also stores all versions of the config, where just the filds of a config
@@ -24,6 +139,7 @@ fn delta_db(m: &Bound<'_, PyModule>) -> PyResult<()> {
 #[pyclass]
 pub struct ConfigStore {
     s: Store,
+    formats: FormatRegistry,
 }
 #[pymethods]
 impl ConfigStore {
@@ -31,37 +147,146 @@ impl ConfigStore {
     /// an existing one at a given url.
     #[new]
     fn new(url: &str) -> PyResult<ConfigStore> {
-        let s = Store::new(url)?;
-        Ok(ConfigStore { s })
+        let s = Store::new(url).map_err(to_py_err)?;
+        Ok(ConfigStore {
+            s,
+            formats: FormatRegistry::default(),
+        })
     }
 
     /// Add a new config by path.
-    fn add_config(&self, path: PathBuf) -> PyResult<()> {
+    ///
+    /// The config is stored under its file name, unless a `name` is given or `root`
+    /// is set, which names it by its path relative to that directory. A `UserWarning`
+    /// is raised when a different path than the one the name was last added from is
+    /// stored under it, including by an earlier `ConfigStore` on the same database.
+    ///
+    /// A file holding several documents, eg. a yaml stream split by `---`, stores each
+    /// one as `<name>#<index>`. Nothing is stored if any of them fails. A file with a
+    /// single document is stored as `<name>`, so one gaining a second document starts
+    /// new configs rather than continuing `<name>`.
+    ///
+    /// :return outcomes list[tuple[str, str, int]]: What was stored for each document as
+    ///  (name, outcome, value), the outcome and value being one of ("new_base", version),
+    ///  ("new_delta", delta_id) or ("unchanged", existing_delta_id).
+    #[pyo3(signature = (path, name=None, root=None))]
+    fn add_config(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        name: Option<String>,
+        root: Option<PathBuf>,
+    ) -> PyResult<Vec<(String, &'static str, i64)>> {
         if !(path.is_file() || path.is_symlink()) {
-            return Err(anyhow!("{} doesn't exist", path.display()).into());
+            let msg = format!("{} doesn't exist", path.display());
+            return Err(to_py_err(
+                io::Error::new(io::ErrorKind::NotFound, msg).into(),
+            ));
         }
-        let json = read_file(&path)?;
-        self.s
-            .add_config(path.clone().file_name().unwrap().to_str().unwrap(), json)?;
-        Ok(())
+        let naming = match (name, root) {
+            (Some(name), _) => Naming::Explicit(name),
+            (None, Some(root)) => Naming::Relative(root),
+            (None, None) => Naming::Basename,
+        };
+        let name = naming.name(&path).map_err(to_py_err)?;
+        let docs = self.formats.read_documents(&path).map_err(to_py_err)?;
+        let named = match docs.len() {
+            0 => {
+                let format = self.formats.by_path(&path).map_or("config", |f| f.name());
+                return Err(to_py_err(DeltaError::Parse {
+                    format: format.to_string(),
+                    source: format!("{} holds no documents", path.display()).into(),
+                }));
+            }
+            1 => vec![(name.clone(), docs.into_iter().next().unwrap())],
+            _ => docs
+                .into_iter()
+                .enumerate()
+                .map(|(idx, doc)| (format!("{}#{}", name, idx), doc))
+                .collect(),
+        };
+        let names = named.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        let outcomes = self.s.add_configs(named).map_err(to_py_err)?;
+        if let Some(previous) = self.s.set_source(&name, &path).map_err(to_py_err)? {
+            let msg = format!(
+                "{} and {} are both stored as config {}",
+                previous.display(),
+                path.canonicalize()?.display(),
+                name
+            );
+            PyErr::warn_bound(py, py.get_type_bound::<PyUserWarning>().as_any(), &msg, 1)?;
+        }
+        Ok(names
+            .into_iter()
+            .zip(outcomes)
+            .map(|(name, outcome)| match outcome {
+                AddOutcome::NewBase { version } => (name, "new_base", version),
+                AddOutcome::NewDelta { id } => (name, "new_delta", id),
+                AddOutcome::Unchanged { existing_id } => (name, "unchanged", existing_id),
+            })
+            .collect())
     }
     /// Get all base configurations and their available versions.
     ///
     /// :return configs list[tuple[str, int]]: List of configs
     ///  as (name, version) tuples.
     fn get_configs(&self) -> PyResult<Vec<(String, i64)>> {
-        Ok(self.s.get_base_configs()?)
+        self.s.get_base_configs().map_err(to_py_err)
     }
     /// Get all the configurations for a specific base config.
     ///
     /// :param name (str): Config's name
     /// :param version (Optional[int]): Version of the config, if None get latest.
+    /// :return configs list[tuple[int, dict]]: The full configs as (delta_id, config)
+    ///  tuples, oldest first.
     #[pyo3(signature = (name, version = None))]
-    fn get_deltas(&self, name: &str, version: Option<i64>) -> PyResult<()> {
-        todo!()
+    fn get_deltas(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        version: Option<i64>,
+    ) -> PyResult<Vec<(i64, PyObject)>> {
+        let unsigned = version
+            .map(u64::try_from)
+            .transpose()
+            .map_err(|_| PyValueError::new_err("version can't be negative"))?;
+        let deltas = self.s.get_all_deltas(name, unsigned).map_err(to_py_err)?;
+        let base = self
+            .s
+            .get_base_config(name, version)
+            .map_err(to_py_err)?
+            .unwrap_or_default();
+        deltas
+            .into_iter()
+            .map(|(id, delta)| {
+                let cfg = build_cfg_from_base_and_delta(base.clone(), delta);
+                Ok((id, json_to_py(py, &cfg)?))
+            })
+            .collect()
     }
-    fn get_latest_config(&self, name: &str, version: Option<i64>) -> PyResult<Option<PyObject>> {
-        todo!();
-        // Ok(self.s.get_latest_config(name, version))?.map(|v| v.into());
+    /// Get the latest config stored under a name.
+    ///
+    /// :param name (str): Config's name
+    /// :param version (Optional[int]): Version of the config, if None the newest config
+    ///  across all versions.
+    /// :return config Optional[dict]: The config, None if nothing is stored.
+    #[pyo3(signature = (name, version = None))]
+    fn get_latest_config(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        version: Option<i64>,
+    ) -> PyResult<Option<PyObject>> {
+        self.s
+            .get_latest_config(name, version)
+            .map_err(to_py_err)?
+            .map(|cfg| json_to_py(py, &cfg))
+            .transpose()
     }
 }
+
+/// Convert a config into the python object `json.loads` makes of it.
+fn json_to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let json = py.import_bound("json")?;
+    Ok(json.call_method1("loads", (value.to_string(),))?.unbind())
+}
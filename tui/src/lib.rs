@@ -220,7 +220,7 @@ impl Widget for &Results {
     }
 }
 impl Results {
-    fn format_line(&self, l: &str, indecies: &[u32]) -> Line {
+    fn format_line(&self, l: &str, indecies: &[u32]) -> Line<'_> {
         Line::from(
             l.char_indices()
                 .map(|(i, c)| match indecies.contains(&(i as u32)) {
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use nucleo::{
     self,
-    pattern::{CaseMatching, MultiPattern, Normalization, Pattern},
+    pattern::{CaseMatching, Normalization, Pattern},
     Config, Nucleo, Utf32String,
 };
 
@@ -24,10 +24,6 @@ impl Matcher {
             false,
         )
     }
-    pub fn get_target(&self) -> &MultiPattern {
-        &self.engine.pattern
-    }
-
     pub fn add_new_strings(&mut self, strings: Vec<String>) {
         let injector = self.engine.injector();
         for string in strings {
@@ -104,6 +100,6 @@ mod test {
         m.add_target("dave");
         m.add_new_strings(t.clone());
         m.tick();
-        assert!(m.get_matches().len() == 0, "{:?}", m.get_matches().len());
+        assert!(m.get_matches().is_empty(), "{:?}", m.get_matches().len());
     }
 }